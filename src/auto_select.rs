@@ -0,0 +1,123 @@
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use rand::SeedableRng;
+use rayon::prelude::*;
+
+use crate::distance_map::{DistanceMap, Metric};
+use crate::held_karp::{estimate_held_karp_memory, is_held_karp_feasible};
+use crate::insertion::get_convex_hull;
+use crate::point::Points;
+use crate::query::{LocalMethod, OptimalMethod, Usage, UserQuery};
+use crate::MAX_MAP_POINTS;
+
+/* Instances up to this size are solved exactly by Held-Karp, whose run time is predictable. */
+const AUTO_HELD_KARP_POINTS: usize = 20;
+
+/* Instances up to this size are solved exactly by LP branch and cut, within the time given. */
+const AUTO_EXACT_POINTS: usize = 100;
+
+/* Points whose nearest neighbor distance is sampled for the clustering measure. */
+const CLUSTERING_SAMPLES: usize = 256;
+
+/* Clark-Evans ratio below which an instance counts as clustered. */
+const CLUSTERED_RATIO: f64 = 0.5;
+
+/* What the automatic mode looks at before picking a solver. */
+pub struct InstanceFeatures {
+    pub point_count: usize,
+    /* Metric the distance map will measure the points by. */
+    pub metric: Metric,
+    /* Clark-Evans ratio: mean nearest neighbor distance over what a uniform random instance of
+    *  the same density would have. Near 1 is uniform, near 0 tightly clustered, above 1 regular. */
+    pub clustering: f64,
+    /* Share of the points on the convex hull. */
+    pub hull_share: f64,
+    /* Points sharing their location with another point. */
+    pub duplicates: usize,
+}
+
+pub fn get_instance_features(points: &Points) -> InstanceFeatures {
+    let count = points.points.len();
+    let metric = DistanceMap::METRIC;
+
+    let (min_x, max_x, min_y, max_y) = points.points.par_iter()
+        .map(|point| (point.x, point.x, point.y, point.y))
+        .reduce(|| (f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY, f64::NEG_INFINITY),
+            |a, b| (a.0.min(b.0), a.1.max(b.1), a.2.min(b.2), a.3.max(b.3)));
+    let area = (max_x - min_x) * (max_y - min_y);
+
+    let samples: Vec<usize> = if count > CLUSTERING_SAMPLES {
+        sample(&mut StdRng::seed_from_u64(0), count, CLUSTERING_SAMPLES).into_vec()
+    } else {
+        (0..count).collect()
+    };
+
+    let nearest: Vec<f64> = samples.par_iter()
+        .map(|&i| {
+            points.points.iter().enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(_, other)| metric.get_distance(&points.points[i], other))
+                .fold(f64::INFINITY, f64::min)
+        })
+        .collect();
+
+    let duplicates = nearest.iter().filter(|&&distance| distance == 0.0).count() * count / samples.len().max(1);
+    let mean_nearest = nearest.iter().sum::<f64>() / nearest.len().max(1) as f64;
+    let expected_nearest = match metric {
+        // Mean nearest neighbor distance of a Poisson process in the plane.
+        Metric::Euclidean => 0.5 * (area / count as f64).sqrt(),
+    };
+    let clustering = if expected_nearest > 0.0 { mean_nearest / expected_nearest } else { 1.0 };
+
+    InstanceFeatures {
+        point_count: count,
+        metric,
+        clustering,
+        hull_share: get_convex_hull(points).len() as f64 / count.max(1) as f64,
+        duplicates,
+    }
+}
+
+/* Turn an 'auto' query into a concrete solve, and print what was chosen and why. */
+pub fn configure_auto(query: &mut UserQuery, features: &InstanceFeatures) {
+    let count = features.point_count;
+    let clustered = features.clustering < CLUSTERED_RATIO;
+
+    let metric = match features.metric {
+        Metric::Euclidean => "Euclidean distances in the plane",
+    };
+    println!("Instance: {} points, {}.", count, metric);
+    println!("Clustering (Clark-Evans ratio, 1 is uniform): {:.3}{}", features.clustering, if clustered { ", clustered" } else { "" });
+    println!("Points on the convex hull: {:.2}%", features.hull_share * 100.0);
+    if features.duplicates > 0 {
+        println!("About {} points share their location with another.", features.duplicates);
+    }
+
+    if count <= AUTO_HELD_KARP_POINTS && is_held_karp_feasible(count, query.memory_limit) {
+        query.usage = Usage::SolveOptimal;
+        query.optimal_method = OptimalMethod::HeldKarp;
+        println!("Chose Held-Karp: {} points is small enough to solve exactly in about {:.1} MiB.",
+            count, estimate_held_karp_memory(count) as f64 / (1024.0 * 1024.0));
+    } else if count <= AUTO_EXACT_POINTS {
+        query.usage = Usage::SolveOptimal;
+        query.optimal_method = OptimalMethod::CuttingPlane;
+        let seconds = *query.time_limit.get_or_insert(query.time as u64);
+        println!("Chose LP branch and cut: {} points is too many for Held-Karp but usually solves exactly. \
+            It stops after {} seconds with the best tour and bound found.", count, seconds);
+    } else if count > MAX_MAP_POINTS {
+        query.usage = Usage::SolveLocal;
+        query.local_method = LocalMethod::Decomposition;
+        println!("Chose spatial decomposition: {} points is too many for a distance map.", count);
+    } else if clustered {
+        query.usage = Usage::SolveLocal;
+        query.local_method = LocalMethod::GuidedLocalSearch;
+        println!("Chose guided local search: the instance is clustered, and its edge penalties get out \
+            of the deep local minima between clusters that restarts keep falling back into.");
+    } else {
+        query.usage = Usage::SolveLocal;
+        query.local_method = LocalMethod::MultiStart;
+        query.islands = true;
+        println!("Chose iterated local search (multi_start with islands): the best all-round method for \
+            {} roughly uniform points. There is no Lin-Kernighan solver, so this stands in for it.", count);
+    }
+}
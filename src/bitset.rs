@@ -0,0 +1,52 @@
+/* Fixed size set of node indices, one bit per node. Cheap to copy when a subtree is handed to
+*  another thread, and iterates in increasing order. */
+#[derive(Clone, Debug)]
+pub struct Bitset {
+    words: Vec<u64>,
+}
+
+impl Bitset {
+    pub fn new(size: usize) -> Bitset {
+        Bitset { words: vec![0; size.div_ceil(64)] }
+    }
+
+    /* Set holding every index in 0..size. */
+    pub fn full(size: usize) -> Bitset {
+        let mut bitset = Bitset::new(size);
+        for i in 0..size as u32 {
+            bitset.insert(i);
+        }
+        bitset
+    }
+
+    pub fn insert(&mut self, i: u32) {
+        self.words[i as usize / 64] |= 1 << (i % 64);
+    }
+
+    pub fn remove(&mut self, i: u32) {
+        self.words[i as usize / 64] &= !(1 << (i % 64));
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.words.iter().enumerate().flat_map(|(index, &word)| {
+            let mut remaining = word;
+            std::iter::from_fn(move || {
+                if remaining == 0 {
+                    return None;
+                }
+
+                let bit = remaining.trailing_zeros();
+                remaining &= remaining - 1;
+                Some(index as u32 * 64 + bit)
+            })
+        })
+    }
+}
@@ -0,0 +1,57 @@
+use rayon::prelude::*;
+
+use crate::distance_map::DistanceMap;
+
+/* Each node's nearest neighbors, closest first. Good tours use almost nothing but these edges,
+*  so searches that only try them run in O(nk) per pass instead of O(n^2). */
+pub struct CandidateGraph {
+    neighbors: Vec<Vec<u32>>,
+}
+
+impl CandidateGraph {
+    pub fn new(map: &DistanceMap, k: usize) -> CandidateGraph {
+        let count = map.point_count();
+        let k = k.min(count.saturating_sub(1));
+
+        let neighbors = (0..count as u32).into_par_iter()
+            .map(|node| {
+                let mut others: Vec<(f64, u32)> = (0..count as u32)
+                    .filter(|&other| other != node)
+                    .map(|other| (map.get_distance_from_points(&node, &other), other))
+                    .collect();
+
+                if k > 0 && k < others.len() {
+                    others.select_nth_unstable_by(k - 1, |a, b| a.0.partial_cmp(&b.0).unwrap());
+                }
+                others.truncate(k);
+                others.sort_unstable_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+                others.into_iter().map(|(_, other)| other).collect()
+            })
+            .collect();
+
+        CandidateGraph { neighbors }
+    }
+
+    /* The two neighbors of each node along a tour, closest first, for searches that should only
+    *  look at the tour's own edges. */
+    pub fn from_tour(map: &DistanceMap, route: &[u32]) -> CandidateGraph {
+        let count = route.len();
+        let mut neighbors: Vec<Vec<u32>> = vec![Vec::with_capacity(2); count];
+
+        for (index, &node) in route.iter().enumerate() {
+            let (previous, next) = (route[(index + count - 1) % count], route[(index + 1) % count]);
+            neighbors[node as usize] = if map.get_distance_from_points(&node, &previous) <= map.get_distance_from_points(&node, &next) {
+                vec![previous, next]
+            } else {
+                vec![next, previous]
+            };
+        }
+
+        CandidateGraph { neighbors }
+    }
+
+    pub fn get_neighbors(&self, node: u32) -> &[u32] {
+        &self.neighbors[node as usize]
+    }
+}
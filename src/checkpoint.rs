@@ -0,0 +1,111 @@
+use std::cell::Cell;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::priority_queue_structs::Branch;
+use crate::query::OptimalMethod;
+use crate::search_limits::SearchStats;
+use crate::solution::Solution;
+
+/* How often a running search saves itself. */
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+/* Work an exact search still has left to do. */
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Frontier {
+    /* Partial routes that have not been searched yet, for branch and bound or best-first. */
+    Branches(Vec<Branch>),
+    /* Held-Karp has filled in every subset up to this size. The table itself is kept next to
+    *  the checkpoint, see `Checkpointer::get_table_filename`. */
+    HeldKarpLayers(usize),
+    /* The search is complete and the incumbent is optimal. */
+    Finished,
+}
+
+/* Everything needed to continue an exact search in a new process. */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub points_filename: String,
+    pub point_count: usize,
+    pub method: OptimalMethod,
+    pub incumbent: Solution,
+    pub nodes_explored: u64,
+    pub nodes_pruned: u64,
+    pub frontier: Frontier,
+}
+
+pub fn load_checkpoint(filename: &str) -> Checkpoint {
+    let file = File::open(filename).expect("File not found");
+
+    serde_json::from_reader(BufReader::new(file)).expect("Error while deserializing")
+}
+
+/* Writes checkpoints of one search to the same file. Files are written next to the target and
+*  renamed over it, so a job killed halfway through a save still leaves the last one intact. */
+pub struct Checkpointer {
+    filename: String,
+    points_filename: String,
+    point_count: usize,
+    method: OptimalMethod,
+    last_save: Cell<Instant>,
+}
+
+impl Checkpointer {
+    pub fn new(filename: &str, points_filename: &str, point_count: usize, method: OptimalMethod) -> Checkpointer {
+        Checkpointer {
+            filename: filename.to_string(),
+            points_filename: points_filename.to_string(),
+            point_count,
+            method,
+            last_save: Cell::new(Instant::now()),
+        }
+    }
+
+    /* Returns true once CHECKPOINT_INTERVAL has passed since the last save. */
+    pub fn is_due(&self) -> bool {
+        self.last_save.get().elapsed() >= CHECKPOINT_INTERVAL
+    }
+
+    pub fn get_table_filename(&self) -> String {
+        format!("{}.table", self.filename)
+    }
+
+    pub fn save(&self, incumbent: &Solution, stats: &SearchStats, frontier: Frontier) {
+        let checkpoint = Checkpoint {
+            points_filename: self.points_filename.clone(),
+            point_count: self.point_count,
+            method: self.method,
+            incumbent: incumbent.clone(),
+            nodes_explored: stats.get_nodes(),
+            nodes_pruned: stats.get_pruned(),
+            frontier,
+        };
+
+        let result = write_atomically(&self.filename, |writer| {
+            serde_json::to_writer(&mut *writer, &checkpoint).map_err(std::io::Error::from)
+        });
+
+        match result {
+            Ok(()) => println!("Saved checkpoint to {}.", self.filename),
+            Err(error) => println!("Failed to save checkpoint to {}: {}", self.filename, error),
+        }
+
+        self.last_save.set(Instant::now());
+    }
+}
+
+/* Write a file through a temporary next to it, then rename it into place. */
+pub fn write_atomically<F>(filename: &str, write: F) -> std::io::Result<()>
+where F: FnOnce(&mut BufWriter<File>) -> std::io::Result<()> {
+    let temporary = format!("{}.tmp", filename);
+    let mut writer = BufWriter::new(File::create(&temporary)?);
+
+    write(&mut writer)?;
+    writer.flush()?;
+    drop(writer);
+
+    fs::rename(&temporary, filename)
+}
@@ -0,0 +1,139 @@
+use rayon::prelude::*;
+
+use crate::distance_map::DistanceMap;
+use crate::matching::get_min_weight_perfect_matching;
+use crate::priority_queue_structs::{DisjointSet, Edge};
+use crate::solution::Solution;
+use crate::get_solution_length;
+
+/* Largest set of odd degree nodes matched exactly. The blossom algorithm is O(n^3), so larger
+*  sets are matched greedily instead, which loses the 1.5 approximation guarantee. */
+const MAX_BLOSSOM_NODES: usize = 1000;
+
+/* Kruskal's minimum spanning tree. Returns the edges of the tree. */
+pub fn get_minimum_spanning_tree(map: &DistanceMap) -> Vec<Edge> {
+    let count = map.point_count() as u32;
+    let edges = map.get_sorted_edges();
+
+    let mut disjoint_set = DisjointSet::new(count as usize);
+    let mut tree: Vec<Edge> = Vec::with_capacity(count as usize);
+
+    for edge in edges {
+        if disjoint_set.find(edge.node1) != disjoint_set.find(edge.node2) {
+            disjoint_set.union(edge.node1, edge.node2);
+            tree.push(edge);
+
+            if tree.len() + 1 == count as usize {
+                break;
+            }
+        }
+    }
+
+    tree
+}
+
+/* Pair up the nodes, shortest pairs first. */
+fn get_greedy_matching(map: &DistanceMap, nodes: &[u32]) -> Vec<(u32, u32)> {
+    let mut pairs: Vec<(f64, usize, usize)> = (0..nodes.len()).into_par_iter()
+        .flat_map_iter(|i| (i + 1..nodes.len()).map(move |j| (map.get_distance_from_points(&nodes[i], &nodes[j]), i, j)))
+        .collect();
+    pairs.par_sort_unstable_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let mut matched: Vec<bool> = vec![false; nodes.len()];
+    let mut matching: Vec<(u32, u32)> = Vec::with_capacity(nodes.len() / 2);
+
+    for (_, i, j) in pairs {
+        if !matched[i] && !matched[j] {
+            matched[i] = true;
+            matched[j] = true;
+            matching.push((nodes[i], nodes[j]));
+        }
+    }
+
+    matching
+}
+
+/* Minimum weight perfect matching of the given nodes, which must be even in number. */
+fn get_matching(map: &DistanceMap, nodes: &[u32]) -> Vec<(u32, u32)> {
+    if nodes.len() > MAX_BLOSSOM_NODES {
+        println!("{} odd degree nodes is too many to match exactly. Matching them greedily.", nodes.len());
+        return get_greedy_matching(map, nodes);
+    }
+
+    let mates = get_min_weight_perfect_matching(nodes.len(), |i, j| map.get_distance_from_points(&nodes[i], &nodes[j]));
+
+    (0..nodes.len())
+        .filter(|&i| i < mates[i])
+        .map(|i| (nodes[i], nodes[mates[i]]))
+        .collect()
+}
+
+/* Hierholzer's algorithm. Every node of the multigraph must have even degree. */
+fn get_euler_tour(count: usize, edges: &[(u32, u32)]) -> Vec<u32> {
+    let mut adjacency: Vec<Vec<(u32, usize)>> = vec![Vec::new(); count];
+    for (id, &(node1, node2)) in edges.iter().enumerate() {
+        adjacency[node1 as usize].push((node2, id));
+        adjacency[node2 as usize].push((node1, id));
+    }
+
+    let mut used: Vec<bool> = vec![false; edges.len()];
+    let mut next: Vec<usize> = vec![0; count];
+    let mut stack: Vec<u32> = vec![0];
+    let mut tour: Vec<u32> = Vec::with_capacity(edges.len() + 1);
+
+    while let Some(&node) = stack.last() {
+        let node = node as usize;
+
+        while next[node] < adjacency[node].len() && used[adjacency[node][next[node]].1] {
+            next[node] += 1;
+        }
+
+        if next[node] == adjacency[node].len() {
+            tour.push(node as u32);
+            stack.pop();
+        } else {
+            let (neighbour, id) = adjacency[node][next[node]];
+            used[id] = true;
+            stack.push(neighbour);
+        }
+    }
+
+    tour
+}
+
+/* Christofides' construction. The minimum spanning tree plus a minimum weight perfect
+*  matching of its odd degree nodes has every degree even, so it has an Euler tour. Skipping
+*  nodes the tour has already visited only shortens it by the triangle inequality, and the
+*  result is at most 1.5 times the optimal tour. */
+pub fn get_christofides(map: &DistanceMap) -> Solution {
+    let count = map.point_count();
+
+    if count < 4 {
+        let route: Vec<u32> = (0..count as u32).collect();
+        let distance = get_solution_length(map, &route).0;
+        return Solution { route, distance };
+    }
+
+    let tree = get_minimum_spanning_tree(map);
+
+    let mut degrees: Vec<usize> = vec![0; count];
+    for edge in tree.iter() {
+        degrees[edge.node1 as usize] += 1;
+        degrees[edge.node2 as usize] += 1;
+    }
+
+    let odd_nodes: Vec<u32> = (0..count as u32).filter(|&node| degrees[node as usize] % 2 == 1).collect();
+
+    let mut edges: Vec<(u32, u32)> = tree.iter().map(|edge| (edge.node1, edge.node2)).collect();
+    edges.extend(get_matching(map, &odd_nodes));
+
+    let mut visited: Vec<bool> = vec![false; count];
+    let route: Vec<u32> = get_euler_tour(count, &edges)
+        .into_iter()
+        .filter(|&node| !std::mem::replace(&mut visited[node as usize], true))
+        .collect();
+
+    let distance = get_solution_length(map, &route).0;
+
+    Solution { route, distance }
+}
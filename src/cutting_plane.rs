@@ -0,0 +1,627 @@
+use std::collections::{HashMap, HashSet};
+
+use rayon::prelude::*;
+
+use crate::distance_map::DistanceMap;
+use crate::priority_queue_structs::DisjointSet;
+use crate::search_limits::SearchStats;
+use crate::solution::Solution;
+use crate::get_solution_length;
+
+/* Nearest neighbors of every node that start out in the LP. Missing edges are priced in later. */
+const CANDIDATE_NEIGHBORS: usize = 10;
+
+/* Tolerances for the simplex and for reading values back out of the LP. */
+const PIVOT_EPSILON: f64 = 1e-9;
+const FEASIBILITY_EPSILON: f64 = 1e-9;
+const VALUE_EPSILON: f64 = 1e-6;
+
+/* Pivots on one tableau before it is rebuilt from its rows to shed accumulated error. */
+const REFACTOR_PIVOTS: usize = 20000;
+
+/* Rebuilds attempted before the LP of a node is given up on. */
+const SOLVE_ATTEMPTS: usize = 5;
+
+/* Dense simplex tableau for min c.x subject to rows a.x <= b and x >= 0. Row i owns the slack
+*  column structural + i, so every slack basis is a valid starting point. Since all edge costs
+*  are non-negative, that basis is dual feasible and the dual simplex can take it from there,
+*  which also lets new cuts be added to a solved tableau and re-optimized in a few pivots. */
+#[derive(Clone)]
+struct Tableau {
+    rows: Vec<Vec<f64>>,
+    rhs: Vec<f64>,
+    reduced: Vec<f64>,
+    basis: Vec<usize>,
+    structural: usize,
+    pivots: usize,
+}
+
+impl Tableau {
+    fn new(costs: &[f64], constraints: &[(Vec<(usize, f64)>, f64)]) -> Tableau {
+        let structural = costs.len();
+        let width = structural + constraints.len();
+
+        let rows: Vec<Vec<f64>> = constraints.par_iter().enumerate()
+            .map(|(i, (coefficients, _))| {
+                let mut row = vec![0.0; width];
+                for (j, a) in coefficients {
+                    row[*j] += a;
+                }
+                row[structural + i] = 1.0;
+                row
+            })
+            .collect();
+
+        let mut reduced = costs.to_vec();
+        reduced.resize(width, 0.0);
+
+        Tableau {
+            rows,
+            rhs: constraints.iter().map(|(_, b)| *b).collect(),
+            reduced,
+            basis: (structural..width).collect(),
+            structural,
+            pivots: 0,
+        }
+    }
+
+    /* Append a.x <= b, rewritten in terms of the current non-basic columns. */
+    fn add_row(&mut self, coefficients: &[(usize, f64)], bound: f64) {
+        let width = self.reduced.len();
+        let mut row = vec![0.0; width + 1];
+        let mut rhs = bound;
+
+        for (j, a) in coefficients {
+            row[*j] += a;
+        }
+
+        for (i, basic_row) in self.rows.iter().enumerate() {
+            let factor = row[self.basis[i]];
+            if factor != 0.0 {
+                for (value, basic) in row.iter_mut().zip(basic_row.iter()) {
+                    *value -= factor * basic;
+                }
+                rhs -= factor * self.rhs[i];
+            }
+        }
+
+        for existing in self.rows.iter_mut() {
+            existing.push(0.0);
+        }
+
+        row[width] = 1.0;
+        self.rows.push(row);
+        self.rhs.push(rhs);
+        self.reduced.push(0.0);
+        self.basis.push(width);
+    }
+
+    fn pivot(&mut self, r: usize, q: usize) {
+        let pivot = self.rows[r][q];
+        let pivot_row: Vec<f64> = self.rows[r].iter()
+            .map(|value| if value.abs() < 1e-13 { 0.0 } else { value / pivot })
+            .collect();
+        let pivot_rhs = self.rhs[r] / pivot;
+
+        self.rows.par_iter_mut().zip(self.rhs.par_iter_mut()).enumerate().for_each(|(i, (row, rhs))| {
+            if i == r {
+                row.copy_from_slice(&pivot_row);
+                *rhs = pivot_rhs;
+            } else {
+                let factor = row[q];
+                if factor != 0.0 {
+                    for (value, pivot_value) in row.iter_mut().zip(pivot_row.iter()) {
+                        *value -= factor * pivot_value;
+                    }
+                    *rhs -= factor * pivot_rhs;
+                }
+            }
+        });
+
+        let factor = self.reduced[q];
+        for (value, pivot_value) in self.reduced.iter_mut().zip(pivot_row.iter()) {
+            *value -= factor * pivot_value;
+        }
+
+        self.basis[r] = q;
+        self.pivots += 1;
+    }
+
+    /* Dual simplex. Returns Some(true) once the tableau is optimal, Some(false) if the LP is
+    *  infeasible, or None if it ran past REFACTOR_PIVOTS and should be rebuilt. */
+    fn dual_simplex(&mut self) -> Option<bool> {
+        loop {
+            let leaving = (0..self.rhs.len())
+                .filter(|&i| self.rhs[i] < -FEASIBILITY_EPSILON)
+                .min_by(|&a, &b| self.rhs[a].partial_cmp(&self.rhs[b]).unwrap());
+
+            let r = match leaving {
+                Some(r) => r,
+                None => return Some(true),
+            };
+
+            let row = &self.rows[r];
+            let mut entering: Option<(usize, f64)> = None;
+
+            for (j, value) in row.iter().enumerate() {
+                if *value < -PIVOT_EPSILON {
+                    let ratio = self.reduced[j].max(0.0) / -value;
+                    let better = match entering {
+                        None => true,
+                        Some((best, best_ratio)) => ratio < best_ratio - 1e-12
+                            || (ratio < best_ratio + 1e-12 && value.abs() > row[best].abs()),
+                    };
+
+                    if better {
+                        entering = Some((j, ratio));
+                    }
+                }
+            }
+
+            match entering {
+                Some((q, _)) => self.pivot(r, q),
+                None => return Some(false),
+            }
+
+            if self.pivots > REFACTOR_PIVOTS {
+                return None;
+            }
+        }
+    }
+
+    fn get_values(&self) -> Vec<f64> {
+        let mut values = vec![0.0; self.structural];
+        for (i, basic) in self.basis.iter().enumerate() {
+            if *basic < self.structural {
+                values[*basic] = self.rhs[i].max(0.0);
+            }
+        }
+        values
+    }
+
+    /* Dual value of every row, read off the reduced costs of the slack columns. */
+    fn get_duals(&self) -> Vec<f64> {
+        (0..self.rhs.len()).map(|i| -self.reduced[self.structural + i]).collect()
+    }
+}
+
+/* Constraints of the TSP relaxation, kept by meaning so they can be rebuilt for a new edge set. */
+#[derive(Clone)]
+enum Row {
+    DegreeUpper(usize),
+    DegreeLower(usize),
+    Subtour(Vec<bool>),
+    FixOne(usize, usize),
+    FixZero(usize, usize),
+}
+
+impl Row {
+    /* Coefficient of edge (u, v) in this row, in <= form. */
+    fn get_coefficient(&self, u: usize, v: usize) -> f64 {
+        match self {
+            Row::DegreeUpper(node) => if u == *node || v == *node { 1.0 } else { 0.0 },
+            Row::DegreeLower(node) => if u == *node || v == *node { -1.0 } else { 0.0 },
+            Row::Subtour(in_set) => if in_set[u] != in_set[v] { -1.0 } else { 0.0 },
+            Row::FixOne(a, b) => if (u, v) == (*a, *b) { -1.0 } else { 0.0 },
+            Row::FixZero(a, b) => if (u, v) == (*a, *b) { 1.0 } else { 0.0 },
+        }
+    }
+
+    fn get_bound(&self) -> f64 {
+        match self {
+            Row::DegreeUpper(_) => 2.0,
+            Row::DegreeLower(_) | Row::Subtour(_) => -2.0,
+            Row::FixOne(_, _) => -1.0,
+            Row::FixZero(_, _) => 0.0,
+        }
+    }
+
+    fn get_coefficients(&self, edges: &[(usize, usize)]) -> Vec<(usize, f64)> {
+        edges.iter().enumerate()
+            .map(|(j, (u, v))| (j, self.get_coefficient(*u, *v)))
+            .filter(|(_, a)| *a != 0.0)
+            .collect()
+    }
+}
+
+/* LP relaxation over a subset of the edges, x(delta(v)) = 2 for every node plus whatever subtour
+*  and branching rows have been added. */
+#[derive(Clone)]
+struct LinearProgram {
+    edges: Vec<(usize, usize)>,
+    edge_index: HashMap<(usize, usize), usize>,
+    costs: Vec<f64>,
+    rows: Vec<Row>,
+    tableau: Tableau,
+}
+
+impl LinearProgram {
+    fn new(matrix: &[Vec<f64>], edges: Vec<(usize, usize)>) -> LinearProgram {
+        let count = matrix.len();
+        let rows: Vec<Row> = (0..count).flat_map(|v| [Row::DegreeUpper(v), Row::DegreeLower(v)]).collect();
+
+        let mut lp = LinearProgram {
+            edges: Vec::new(),
+            edge_index: HashMap::new(),
+            costs: Vec::new(),
+            rows,
+            tableau: Tableau::new(&[], &[]),
+        };
+        lp.add_edges(matrix, edges);
+        lp
+    }
+
+    /* Add columns and rebuild the tableau from scratch. */
+    fn add_edges(&mut self, matrix: &[Vec<f64>], edges: Vec<(usize, usize)>) {
+        for (u, v) in edges {
+            if !self.edge_index.contains_key(&(u, v)) {
+                self.edge_index.insert((u, v), self.edges.len());
+                self.edges.push((u, v));
+                self.costs.push(matrix[u][v]);
+            }
+        }
+
+        self.rebuild();
+    }
+
+    fn rebuild(&mut self) {
+        let constraints: Vec<(Vec<(usize, f64)>, f64)> = self.rows.par_iter()
+            .map(|row| (row.get_coefficients(&self.edges), row.get_bound()))
+            .collect();
+
+        self.tableau = Tableau::new(&self.costs, &constraints);
+    }
+
+    fn add_row(&mut self, row: Row) {
+        self.tableau.add_row(&row.get_coefficients(&self.edges), row.get_bound());
+        self.rows.push(row);
+    }
+
+    /* Returns whether the LP is feasible, or None if the simplex kept failing to converge even
+    *  after rebuilding the tableau. */
+    fn solve(&mut self) -> Option<bool> {
+        for _ in 0..SOLVE_ATTEMPTS {
+            if let Some(feasible) = self.tableau.dual_simplex() {
+                return Some(feasible);
+            }
+            self.rebuild();
+        }
+
+        None
+    }
+
+    fn get_objective(&self, values: &[f64]) -> f64 {
+        values.iter().zip(self.costs.iter()).map(|(x, c)| x * c).sum()
+    }
+}
+
+/* Subtour elimination separation on the support graph of the LP solution. If the support graph
+*  is disconnected, every component is a violated cut. Otherwise Stoer-Wagner finds the minimum
+*  cut, and every cut-of-the-phase lighter than two along the way is returned as well. */
+fn separate_subtours(count: usize, edges: &[(usize, usize)], values: &[f64]) -> Vec<Vec<bool>> {
+    let mut disjoint_set = DisjointSet::new(count);
+    for ((u, v), x) in edges.iter().zip(values.iter()) {
+        if *x > VALUE_EPSILON {
+            disjoint_set.union(*u as u32, *v as u32);
+        }
+    }
+
+    let mut components: HashMap<u32, Vec<bool>> = HashMap::new();
+    for node in 0..count {
+        let root = disjoint_set.find(node as u32);
+        components.entry(root).or_insert_with(|| vec![false; count])[node] = true;
+    }
+
+    if components.len() > 1 {
+        return components.into_values().collect();
+    }
+
+    let mut weights: Vec<Vec<f64>> = vec![vec![0.0; count]; count];
+    for ((u, v), x) in edges.iter().zip(values.iter()) {
+        weights[*u][*v] += x;
+        weights[*v][*u] += x;
+    }
+
+    let mut members: Vec<Vec<usize>> = (0..count).map(|v| vec![v]).collect();
+    let mut active: Vec<usize> = (0..count).collect();
+    let mut cuts: Vec<Vec<bool>> = Vec::new();
+
+    while active.len() > 1 {
+        let mut connection: Vec<f64> = vec![0.0; count];
+        let mut added: Vec<bool> = vec![false; count];
+        let (mut previous, mut last) = (active[0], active[0]);
+
+        for _ in 0..active.len() {
+            let next = *active.iter()
+                .filter(|&&v| !added[v])
+                .max_by(|&&a, &&b| connection[a].partial_cmp(&connection[b]).unwrap())
+                .unwrap();
+
+            added[next] = true;
+            previous = last;
+            last = next;
+
+            for &v in active.iter().filter(|&&v| !added[v]) {
+                connection[v] += weights[next][v];
+            }
+        }
+
+        if connection[last] < 2.0 - VALUE_EPSILON {
+            let mut in_set = vec![false; count];
+            for &v in members[last].iter() {
+                in_set[v] = true;
+            }
+            cuts.push(in_set);
+        }
+
+        // Merge the last node of the phase into the one before it.
+        let merged = std::mem::take(&mut members[last]);
+        members[previous].extend(merged);
+        let last_weights = weights[last].clone();
+        for (v, weight) in last_weights.iter().enumerate() {
+            weights[previous][v] += weight;
+            weights[v][previous] = weights[previous][v];
+        }
+        weights[previous][previous] = 0.0;
+        active.retain(|&v| v != last);
+    }
+
+    cuts
+}
+
+/* Edges outside of the LP whose reduced cost under the current duals is negative, most negative
+*  first. If there are none, the LP bound holds for the complete graph. */
+fn price_edges(matrix: &[Vec<f64>], lp: &LinearProgram, forbidden: &HashSet<(usize, usize)>) -> Vec<(usize, usize)> {
+    let count = matrix.len();
+    let duals = lp.tableau.get_duals();
+
+    let mut potential: Vec<f64> = vec![0.0; count];
+    let mut subtours: Vec<(&Vec<bool>, f64)> = Vec::new();
+
+    for (row, dual) in lp.rows.iter().zip(duals.iter()) {
+        match row {
+            Row::DegreeUpper(v) => potential[*v] += dual,
+            Row::DegreeLower(v) => potential[*v] -= dual,
+            Row::Subtour(in_set) if dual.abs() > 1e-12 => subtours.push((in_set, *dual)),
+            _ => {}
+        }
+    }
+
+    let mut candidates: Vec<(f64, usize, usize)> = (0..count).into_par_iter()
+        .flat_map_iter(|u| {
+            let potential = &potential;
+            let subtours = &subtours;
+
+            (u + 1..count).filter_map(move |v| {
+                if lp.edge_index.contains_key(&(u, v)) || forbidden.contains(&(u, v)) {
+                    return None;
+                }
+
+                let crossing: f64 = subtours.iter()
+                    .filter(|(in_set, _)| in_set[u] != in_set[v])
+                    .map(|(_, dual)| dual)
+                    .sum();
+                let reduced_cost = matrix[u][v] - potential[u] - potential[v] + crossing;
+
+                if reduced_cost < -1e-7 { Some((reduced_cost, u, v)) } else { None }
+            })
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    candidates.truncate(count.max(50));
+    candidates.into_iter().map(|(_, u, v)| (u, v)).collect()
+}
+
+/* Edges outside of the LP that may still be used, for when pricing can't say which ones help. */
+fn get_missing_edges(count: usize, lp: &LinearProgram, forbidden: &HashSet<(usize, usize)>) -> Vec<(usize, usize)> {
+    (0..count)
+        .flat_map(|u| (u + 1..count).map(move |v| (u, v)))
+        .filter(|edge| !lp.edge_index.contains_key(edge) && !forbidden.contains(edge))
+        .collect()
+}
+
+/* Lower bound on every tour from the current duals, whether or not the LP has been solved or
+*  every edge priced in. For duals y <= 0 on the rows a.x <= b, any x between 0 and 1 costs at
+*  least y.b plus the negative reduced costs c - y.a over all edges. */
+fn get_dual_bound(matrix: &[Vec<f64>], lp: &LinearProgram, forbidden: &HashSet<(usize, usize)>) -> f64 {
+    let count = matrix.len();
+    let duals: Vec<f64> = lp.tableau.get_duals().iter().map(|dual| dual.min(0.0)).collect();
+
+    let constant: f64 = lp.rows.iter().zip(duals.iter()).map(|(row, dual)| dual * row.get_bound()).sum();
+    let negative: f64 = (0..count).into_par_iter()
+        .map(|u| {
+            (u + 1..count)
+                .filter(|&v| !forbidden.contains(&(u, v)))
+                .map(|v| {
+                    let priced: f64 = lp.rows.iter().zip(duals.iter()).map(|(row, dual)| dual * row.get_coefficient(u, v)).sum();
+                    (matrix[u][v] - priced).min(0.0)
+                })
+                .sum::<f64>()
+        })
+        .sum();
+
+    constant + negative
+}
+
+/* Returns the tour if the LP solution is integral and forms a single cycle. */
+fn get_tour(count: usize, edges: &[(usize, usize)], values: &[f64]) -> Option<Vec<u32>> {
+    let mut neighbors: Vec<Vec<usize>> = vec![Vec::new(); count];
+
+    for ((u, v), x) in edges.iter().zip(values.iter()) {
+        if *x > 1.0 - VALUE_EPSILON {
+            neighbors[*u].push(*v);
+            neighbors[*v].push(*u);
+        } else if *x > VALUE_EPSILON {
+            return None;
+        }
+    }
+
+    if neighbors.iter().any(|adjacent| adjacent.len() != 2) {
+        return None;
+    }
+
+    let mut route: Vec<u32> = vec![0];
+    let (mut previous, mut current) = (0, neighbors[0][0]);
+
+    while current != 0 {
+        route.push(current as u32);
+        let next = if neighbors[current][0] == previous { neighbors[current][1] } else { neighbors[current][0] };
+        previous = current;
+        current = next;
+    }
+
+    if route.len() == count { Some(route) } else { None }
+}
+
+pub struct CuttingPlaneStats {
+    pub root_bound: f64,
+    pub cuts: usize,
+    pub priced_edges: usize,
+    pub failed_nodes: usize,
+}
+
+/* Cut, price and branch on one node of the search tree until it is pruned, integral or split. */
+fn branch_and_cut(mut lp: LinearProgram,
+    matrix: &[Vec<f64>],
+    forbidden: &HashSet<(usize, usize)>,
+    best_solution: &mut Solution,
+    stats: &mut CuttingPlaneStats,
+    search_stats: &SearchStats,) {
+
+    let count = matrix.len();
+    let is_root = search_stats.get_nodes() == 0;
+
+    if !search_stats.visit() {
+        return;
+    }
+
+    let values = loop {
+        // Re-check the limits without counting a node, a single LP can take a while.
+        if !search_stats.visit_many(0) {
+            if is_root {
+                stats.root_bound = get_dual_bound(matrix, &lp, forbidden);
+            }
+            return;
+        }
+
+        match lp.solve() {
+            Some(true) => {}
+            Some(false) => {
+                // Infeasible over the edges in the LP says nothing about the others, so only
+                // prune once none are left out.
+                let missing = get_missing_edges(count, &lp, forbidden);
+                if missing.is_empty() {
+                    return;
+                }
+                stats.priced_edges += missing.len();
+                lp.add_edges(matrix, missing);
+                continue;
+            }
+            None => {
+                stats.failed_nodes += 1;
+                search_stats.stop();
+                if is_root {
+                    stats.root_bound = get_dual_bound(matrix, &lp, forbidden);
+                }
+                return;
+            }
+        }
+
+        let values = lp.tableau.get_values();
+
+        let cuts = separate_subtours(count, &lp.edges, &values);
+        if !cuts.is_empty() {
+            stats.cuts += cuts.len();
+            for in_set in cuts {
+                lp.add_row(Row::Subtour(in_set));
+            }
+            continue;
+        }
+
+        let new_edges = price_edges(matrix, &lp, forbidden);
+        if !new_edges.is_empty() {
+            stats.priced_edges += new_edges.len();
+            lp.add_edges(matrix, new_edges);
+            continue;
+        }
+
+        break values;
+    };
+
+    let objective = lp.get_objective(&values);
+    if is_root {
+        stats.root_bound = objective;
+    }
+
+    if objective >= best_solution.distance - VALUE_EPSILON {
+        search_stats.prune();
+        return;
+    }
+
+    if let Some(route) = get_tour(count, &lp.edges, &values) {
+        best_solution.distance = route.windows(2).map(|pair| matrix[pair[0] as usize][pair[1] as usize]).sum::<f64>()
+            + matrix[route[count - 1] as usize][route[0] as usize];
+        best_solution.route = route;
+        return;
+    }
+
+    // Branch on the most fractional edge, trying to fix it into the tour first.
+    let (branch_index, _) = values.iter().enumerate()
+        .map(|(j, x)| (j, (x - 0.5).abs()))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .unwrap();
+    let (u, v) = lp.edges[branch_index];
+
+    let mut included = lp.clone();
+    included.add_row(Row::FixOne(u, v));
+    branch_and_cut(included, matrix, forbidden, best_solution, stats, search_stats);
+
+    let mut excluded_forbidden = forbidden.clone();
+    excluded_forbidden.insert((u, v));
+    lp.add_row(Row::FixZero(u, v));
+    branch_and_cut(lp, matrix, &excluded_forbidden, best_solution, stats, search_stats);
+}
+
+/* Branch and cut on the LP relaxation of the TSP. The LP starts from the degree constraints over
+*  each node's nearest neighbors plus the edges of `best_solution`, and is tightened with subtour
+*  elimination cuts found by minimum cut separation. Edges left out are priced in whenever their
+*  reduced cost is negative, so a node is only pruned on a bound that holds for every edge.
+*  A node whose LP is infeasible gets every edge it may still use before it is pruned.
+*  Fractional solutions are branched on. `best_solution` is the starting upper bound, and is
+*  returned unchanged if it is already optimal. The root LP bound is reported in the stats, or
+*  the bound from its duals so far if the search stops before the root LP is done. */
+pub fn get_cutting_plane(map: &DistanceMap, best_solution: &Solution, search_stats: &SearchStats) -> (Solution, CuttingPlaneStats) {
+    let count = map.point_count();
+    let mut stats = CuttingPlaneStats { root_bound: 0.0, cuts: 0, priced_edges: 0, failed_nodes: 0 };
+    let mut solution = best_solution.clone();
+
+    if count < 4 {
+        return (solution, stats);
+    }
+
+    let matrix = map.get_matrix();
+
+    let mut edges: HashSet<(usize, usize)> = HashSet::new();
+    for (u, row) in matrix.iter().enumerate() {
+        let mut neighbors: Vec<usize> = (0..count).filter(|&v| v != u).collect();
+        neighbors.sort_by(|&a, &b| row[a].partial_cmp(&row[b]).unwrap());
+
+        for &v in neighbors.iter().take(CANDIDATE_NEIGHBORS) {
+            edges.insert((u.min(v), u.max(v)));
+        }
+    }
+    for k in 0..solution.len() {
+        let (u, v) = (solution.route[k] as usize, solution.route[(k + 1) % count] as usize);
+        edges.insert((u.min(v), u.max(v)));
+    }
+
+    let mut edges: Vec<(usize, usize)> = edges.into_iter().collect();
+    edges.sort();
+
+    let lp = LinearProgram::new(&matrix, edges);
+    branch_and_cut(lp, &matrix, &HashSet::new(), &mut solution, &mut stats, search_stats);
+
+    solution.distance = get_solution_length(map, &solution.route).0;
+    (solution, stats)
+}
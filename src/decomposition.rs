@@ -0,0 +1,248 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use rayon::prelude::*;
+
+use crate::distance_map::{DistanceMap, EdgeCost, FIXED_EDGE_COST};
+use crate::held_karp::get_held_karp;
+use crate::nearest_neighbor::get_nearest_neighbor_from;
+use crate::point::{Point, Points};
+use crate::search_limits::{SearchLimits, SearchStats};
+use crate::solution::Solution;
+use crate::space_filling_curve::{get_curve_order, SpaceFillingCurve};
+use crate::{activate_cities, local_descent};
+
+/* Cells this small are solved exactly by Held-Karp. */
+const EXACT_CELL_POINTS: usize = 12;
+
+/* Distances between the points of one cell or window, indexed locally. */
+struct DenseCost {
+    matrix: Vec<Vec<f64>>,
+}
+
+impl DenseCost {
+    fn new(points: &Points, nodes: &[u32]) -> DenseCost {
+        let matrix = nodes.iter()
+            .map(|&i| nodes.iter().map(|&j| get_point_distance(points, i, j)).collect())
+            .collect();
+
+        DenseCost { matrix }
+    }
+}
+
+impl EdgeCost for DenseCost {
+    fn get_distance_from_points(&self, point1: &u32, point2: &u32) -> f64 {
+        self.matrix[*point1 as usize][*point2 as usize]
+    }
+}
+
+fn get_point_distance(points: &Points, a: u32, b: u32) -> f64 {
+    DistanceMap::get_distance(&points.points[a as usize], &points.points[b as usize])
+}
+
+fn get_subset(points: &Points, nodes: &[u32]) -> Points {
+    Points { points: nodes.iter().map(|&node| points.points[node as usize].clone()).collect() }
+}
+
+/* Recursively split the nodes at the median of the longer side of their bounding box until
+*  every cell holds at most `cell_size` of them. */
+fn get_cells(points: &Points, nodes: &mut [u32], cell_size: usize) -> Vec<Vec<u32>> {
+    if nodes.len() <= cell_size {
+        return vec![nodes.to_vec()];
+    }
+
+    let (min_x, max_x, min_y, max_y) = nodes.iter()
+        .map(|&node| &points.points[node as usize])
+        .fold((f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY, f64::NEG_INFINITY),
+            |a, point| (a.0.min(point.x), a.1.max(point.x), a.2.min(point.y), a.3.max(point.y)));
+
+    let split_x = max_x - min_x >= max_y - min_y;
+    let coordinate = |node: &u32| {
+        let point = &points.points[*node as usize];
+        if split_x { point.x } else { point.y }
+    };
+
+    let middle = nodes.len() / 2;
+    nodes.select_nth_unstable_by(middle, |a, b| coordinate(a).partial_cmp(&coordinate(b)).unwrap());
+
+    let (low, high) = nodes.split_at_mut(middle);
+    let (mut low_cells, high_cells) = rayon::join(|| get_cells(points, low, cell_size), || get_cells(points, high, cell_size));
+
+    low_cells.extend(high_cells);
+    low_cells
+}
+
+/* Tour of one cell, as a route over the global node indices. */
+fn solve_cell(points: &Points, cell: &[u32], deadline: Instant) -> Vec<u32> {
+    if cell.len() <= 3 {
+        return cell.to_vec();
+    }
+
+    if cell.len() <= EXACT_CELL_POINTS {
+        let map = DistanceMap::new(&get_subset(points, cell));
+        let stats = SearchStats::new(SearchLimits::new(None, None));
+        let solution = get_held_karp(&map, &stats, None, &Solution::new(), 0).unwrap();
+
+        return solution.route.iter().map(|&local| cell[local as usize]).collect();
+    }
+
+    let cost = DenseCost::new(points, cell);
+    let mut solution = get_nearest_neighbor_from(&cost.matrix, 0);
+
+    let mut active: VecDeque<u32> = VecDeque::new();
+    let mut queued: Vec<bool> = vec![false; cell.len()];
+    activate_cities(&solution.route, &mut active, &mut queued);
+    local_descent(&cost, &mut solution, &mut active, &mut queued, deadline);
+
+    solution.route.iter().map(|&local| cell[local as usize]).collect()
+}
+
+/* Splice the cell's tour into the tour so far, held as successors in `next`. One edge (a, b)
+*  leaving a node of the previous cell and one edge (c, d) of the new tour are swapped for two
+*  edges that join the cycles, choosing the pair and direction that add the least. */
+fn merge_cell(points: &Points, next: &mut [u32], previous: &[u32], route: &[u32]) {
+    let length = route.len();
+
+    let (a, k, reversed, _) = previous.par_iter()
+        .map(|&a| {
+            let b = next[a as usize];
+            let removed_ab = get_point_distance(points, a, b);
+
+            (0..length).flat_map(|k| {
+                let (c, d) = (route[k], route[(k + 1) % length]);
+                let removed = removed_ab + get_point_distance(points, c, d);
+
+                // Either a -> d ... c -> b forwards, or a -> c ... d -> b backwards.
+                [(a, k, false, get_point_distance(points, a, d) + get_point_distance(points, c, b) - removed),
+                    (a, k, true, get_point_distance(points, a, c) + get_point_distance(points, d, b) - removed)]
+            }).min_by(|x, y| x.3.partial_cmp(&y.3).unwrap()).unwrap()
+        })
+        .min_by(|x, y| x.3.partial_cmp(&y.3).unwrap())
+        .unwrap();
+
+    let b = next[a as usize];
+    let (c, d) = (route[k], route[(k + 1) % length]);
+
+    for i in 0..length {
+        let (from, to) = (route[i], route[(i + 1) % length]);
+        if reversed {
+            next[to as usize] = from;
+        } else {
+            next[from as usize] = to;
+        }
+    }
+
+    if reversed {
+        next[a as usize] = c;
+        next[d as usize] = b;
+    } else {
+        next[a as usize] = d;
+        next[c as usize] = b;
+    }
+}
+
+/* Improve a stretch of the tour without moving its two ends. The stretch is closed into a cycle
+*  through an edge that is pinned in place, then cut open at that edge again. */
+fn improve_path(points: &Points, path: &mut [u32], deadline: Instant) {
+    let length = path.len();
+
+    if length < 5 {
+        return;
+    }
+
+    let mut cost = DenseCost::new(points, path);
+    cost.matrix[0][length - 1] = FIXED_EDGE_COST;
+    cost.matrix[length - 1][0] = FIXED_EDGE_COST;
+
+    let mut solution = Solution { route: (0..length as u32).collect(), distance: 0.0 };
+    let mut active: VecDeque<u32> = VecDeque::new();
+    let mut queued: Vec<bool> = vec![false; length];
+    activate_cities(&solution.route, &mut active, &mut queued);
+    local_descent(&cost, &mut solution, &mut active, &mut queued, deadline);
+
+    // Read the cycle from the first end away from the pinned edge.
+    let start = solution.route.iter().position(|&local| local == 0).unwrap();
+    let forwards = solution.route[(start + 1) % length] != length as u32 - 1;
+    let local_path: Vec<u32> = (0..length)
+        .map(|step| if forwards { (start + step) % length } else { (start + length - step) % length })
+        .map(|index| solution.route[index])
+        .collect();
+
+    let original = path.to_vec();
+    for (node, local) in path.iter_mut().zip(local_path) {
+        *node = original[local as usize];
+    }
+}
+
+fn get_route_length(points: &Points, route: &[u32]) -> f64 {
+    (0..route.len()).into_par_iter()
+        .map(|i| get_point_distance(points, route[i], route[(i + 1) % route.len()]))
+        .sum()
+}
+
+/* Karp-style divide and conquer for instances far too large for a distance map. The points are
+*  split into cells of at most `cell_size`, every cell is solved on its own in parallel, and the
+*  cell tours are spliced together in Hilbert order of the cells' centres, so each is joined to
+*  a neighbour.
+*
+*  The joins and the cell borders are then improved by optimizing windows of `cell_size`
+*  consecutive cities with their ends fixed, again in parallel. Every pass shifts the windows
+*  by half a window, so each seam lands inside one, until a pass stops helping or the time is up. */
+pub fn get_decomposition(points: &Points, cell_size: usize, deadline: Instant) -> Solution {
+    let count = points.points.len();
+    let cell_size = cell_size.max(EXACT_CELL_POINTS);
+
+    let mut nodes: Vec<u32> = (0..count as u32).collect();
+    let cells = get_cells(points, &mut nodes, cell_size);
+    println!("Split {} points into {} cells.", count, cells.len());
+
+    let routes: Vec<Vec<u32>> = cells.par_iter().map(|cell| solve_cell(points, cell, deadline)).collect();
+
+    let centres = Points {
+        points: cells.iter().enumerate().map(|(id, cell)| {
+            let (x, y) = cell.iter().fold((0.0, 0.0), |sum, &node| {
+                (sum.0 + points.points[node as usize].x, sum.1 + points.points[node as usize].y)
+            });
+            Point { x: x / cell.len() as f64, y: y / cell.len() as f64, id: id as u32 }
+        }).collect(),
+    };
+    let order = get_curve_order(&centres, SpaceFillingCurve::Hilbert);
+
+    let mut next: Vec<u32> = vec![0; count];
+    let first = &routes[order[0] as usize];
+    for i in 0..first.len() {
+        next[first[i] as usize] = first[(i + 1) % first.len()];
+    }
+
+    for pair in order.windows(2) {
+        merge_cell(points, &mut next, &cells[pair[0] as usize], &routes[pair[1] as usize]);
+    }
+
+    let mut route: Vec<u32> = Vec::with_capacity(count);
+    let mut node = 0;
+    while route.len() < count {
+        route.push(node);
+        node = next[node as usize];
+    }
+
+    let mut distance = get_route_length(points, &route);
+    println!("Stitched the cells into a tour of length {}.", distance);
+
+    let mut pass = 0;
+    while count > cell_size && Instant::now() < deadline {
+        // Shifting by half a window every pass alternates between the two alignments.
+        route.rotate_left(cell_size / 2);
+        route.par_chunks_mut(cell_size).for_each(|window| improve_path(points, window, deadline));
+
+        let new_distance = get_route_length(points, &route);
+        let improved = distance - new_distance;
+        distance = new_distance;
+        pass += 1;
+
+        if improved <= distance * 1e-6 && pass >= 2 {
+            break;
+        }
+    }
+
+    Solution { route, distance }
+}
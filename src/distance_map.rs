@@ -3,6 +3,31 @@ use rayon::prelude::*;
 
 use crate::point::Point;
 use crate::point::Points;
+use crate::priority_queue_structs::Edge;
+
+/* Anything that can price the edge between two points. The local search moves are written
+*  against this so they can run on augmented costs as well as plain distances. */
+pub trait EdgeCost {
+    fn get_distance_from_points(&self, point1: &u32, point2: &u32) -> f64;
+}
+
+/* Cost an EdgeCost gives an edge to pin it into every local minimum. No move that removes it
+*  can improve. */
+pub const FIXED_EDGE_COST: f64 = -1e12;
+
+/* How the distance between two points is measured. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Metric {
+    Euclidean,
+}
+
+impl Metric {
+    pub fn get_distance(&self, point1: &Point, point2: &Point) -> f64 {
+        match self {
+            Metric::Euclidean => DistanceMap::get_distance(point1, point2),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct DistanceMap {
@@ -11,6 +36,9 @@ pub struct DistanceMap {
 }
 
 impl DistanceMap {
+    /* The metric every map measures its points by. */
+    pub const METRIC: Metric = Metric::Euclidean;
+
     /* Calculate the points and store in distance map, then return map */
     pub fn new(points: &Points) -> DistanceMap {
         let num_points = points.points.len() as u32;
@@ -18,7 +46,7 @@ impl DistanceMap {
 
         points.points.par_iter().enumerate().for_each(|(i, point1)| {
             for (j, point2) in points.points.iter().enumerate().skip(i + 1) {
-                let distance = DistanceMap::get_distance(point1, point2);
+                let distance = DistanceMap::METRIC.get_distance(point1, point2);
                 map.insert((i as u32, j as u32), distance);
             }
         });
@@ -26,6 +54,17 @@ impl DistanceMap {
         DistanceMap { map, num_points }
     }
     
+    /* Append a city that is not in the plane, given its distance to each of the others. */
+    pub fn add_point(&mut self, distances: &[f64]) {
+        let node = self.num_points;
+
+        distances.par_iter().enumerate().for_each(|(i, &distance)| {
+            self.map.insert((i as u32, node), distance);
+        });
+
+        self.num_points += 1;
+    }
+
     pub fn point_count(&self) -> usize {
         self.num_points as usize
     }
@@ -49,7 +88,28 @@ impl DistanceMap {
         self.map.get(&(*smaller, *larger)).map_or(0.0, |v| *v)
     }
 
-    fn get_distance(point1: &Point, point2: &Point) -> f64 {
+    /* Copy the distances into a dense matrix for the solvers that index them in tight loops. */
+    pub fn get_matrix(&self) -> Vec<Vec<f64>> {
+        let count = self.point_count();
+
+        (0..count as u32).into_par_iter()
+            .map(|i| (0..count as u32).map(|j| self.get_distance_from_points(&i, &j)).collect())
+            .collect()
+    }
+
+    /* Every edge between two points, shortest first. */
+    pub fn get_sorted_edges(&self) -> Vec<Edge> {
+        let count = self.point_count() as u32;
+
+        let mut edges: Vec<Edge> = (0..count).into_par_iter()
+            .flat_map_iter(|node1| (node1 + 1..count).map(move |node2| Edge { node1, node2, distance: self.get_distance_from_points(&node1, &node2) }))
+            .collect();
+        edges.par_sort_unstable_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+
+        edges
+    }
+
+    pub fn get_distance(point1: &Point, point2: &Point) -> f64 {
 
         /* Emperical testing with a random, even distribution of points shows that NOT taking the square root
         * yields a 1% slower distance and greedy computation time compared to taking the square root. Evidently, 
@@ -58,4 +118,10 @@ impl DistanceMap {
         *******************************/
         (f64::powf(point1.x-point2.x,2.0) + f64::powf(point1.y-point2.y,2.0)).sqrt()
     }    
-}
\ No newline at end of file
+}
+
+impl EdgeCost for DistanceMap {
+    fn get_distance_from_points(&self, point1: &u32, point2: &u32) -> f64 {
+        DistanceMap::get_distance_from_points(self, point1, point2)
+    }
+}
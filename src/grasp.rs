@@ -0,0 +1,168 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::distance_map::DistanceMap;
+use crate::solution::Solution;
+use crate::{get_local_descent, get_solution_length};
+
+/* Number of distinct local minima kept around as path relinking guides. */
+const ELITE_SIZE: usize = 10;
+
+/* Randomized nearest neighbor construction. Starts at a random node, and at every step picks
+*  uniformly from the restricted candidate list: the unvisited nodes no farther than
+*  min + alpha * (max - min). Alpha 0.0 is the plain greedy walk, 1.0 is a random tour. */
+pub fn get_randomized_greedy<R: Rng>(map: &DistanceMap, alpha: f64, rng: &mut R) -> Solution {
+    let count = map.point_count();
+    let mut solution: Vec<u32> = Vec::with_capacity(count);
+    let mut unvisited: Vec<u32> = (0..count as u32).collect();
+
+    if count == 0 {
+        return Solution::new();
+    }
+
+    let first = unvisited.swap_remove(rng.gen_range(0..count));
+    solution.push(first);
+
+    while !unvisited.is_empty() {
+        let current_node = *solution.last().unwrap();
+
+        let distances: Vec<f64> = unvisited.iter()
+            .map(|node| map.get_distance_from_points(&current_node, node))
+            .collect();
+
+        let min = distances.iter().cloned().fold(f64::MAX, f64::min);
+        let max = distances.iter().cloned().fold(f64::MIN, f64::max);
+        let threshold = min + alpha * (max - min);
+
+        let candidates: Vec<usize> = (0..unvisited.len())
+            .filter(|&index| distances[index] <= threshold)
+            .collect();
+
+        let chosen = candidates[rng.gen_range(0..candidates.len())];
+        solution.push(unvisited.swap_remove(chosen));
+    }
+
+    let distance: f64 = get_solution_length(map, &solution).0;
+
+    Solution { route: solution, distance }
+}
+
+/* Rotate a route so it starts at the given node. */
+fn rotate_to(route: &[u32], node: u32) -> Vec<u32> {
+    let index = route.iter().position(|&n| n == node).unwrap_or(0);
+    let mut rotated = route[index..].to_vec();
+    rotated.extend_from_slice(&route[..index]);
+    rotated
+}
+
+/* Walk from `initial` towards `guide` by swapping one city into its guide position at a time,
+*  and return the best intermediate tour that differs from both ends. */
+fn path_relink(map: &DistanceMap, initial: &Solution, guide: &Solution) -> Option<Solution> {
+    let mut current = initial.route.clone();
+    let target = rotate_to(&guide.route, current[0]);
+
+    let mut position: Vec<usize> = vec![0; current.len()];
+    for (index, node) in current.iter().enumerate() {
+        position[*node as usize] = index;
+    }
+
+    let mut best: Option<Solution> = None;
+
+    for index in 1..current.len() {
+        if current[index] == target[index] {
+            continue;
+        }
+
+        let other = position[target[index] as usize];
+        position[current[index] as usize] = other;
+        position[target[index] as usize] = index;
+        current.swap(index, other);
+
+        if current == target {
+            break;
+        }
+
+        let distance = get_solution_length(map, &current).0;
+        if best.as_ref().is_none_or(|solution| distance < solution.distance) {
+            best = Some(Solution { route: current.clone(), distance });
+        }
+    }
+
+    best
+}
+
+/* Offer a local minima to the elite pool. Keeps the pool sorted and free of duplicates. */
+fn update_elite(elite: &Mutex<Vec<Solution>>, solution: &Solution) {
+    let mut elite_guard = elite.lock().unwrap();
+
+    if elite_guard.iter().any(|other| (other.distance - solution.distance).abs() < 1e-7) {
+        return;
+    }
+
+    if elite_guard.len() < ELITE_SIZE || solution.distance < elite_guard.last().unwrap().distance {
+        elite_guard.push(solution.clone());
+        elite_guard.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+        elite_guard.truncate(ELITE_SIZE);
+    }
+}
+
+/* Greedy randomized adaptive search procedure. Every rayon thread repeatedly builds a
+*  randomized greedy tour and descends it to a local minima until `max_duration` has passed.
+*  With `path_relinking`, each new minima is also relinked towards a random elite tour and
+*  the best tour on that path is descended as well.
+*
+*  A complete `initial` tour is the best to beat, and joins the elite tours to relink towards. */
+pub fn get_grasp(map: &DistanceMap, initial: &Solution, alpha: f64, path_relinking: bool, seed: u64, max_duration: Duration) -> Solution {
+    let deadline = Instant::now() + max_duration;
+    let best_solution = Mutex::new(Solution { route: Vec::new(), distance: f64::MAX });
+    let elite: Mutex<Vec<Solution>> = Mutex::new(Vec::new());
+
+    if initial.route.len() == map.point_count() {
+        update_elite(&elite, initial);
+        *best_solution.lock().unwrap() = initial.clone();
+    }
+
+    (0..rayon::current_num_threads()).into_par_iter().for_each(|worker| {
+        let mut rng = StdRng::seed_from_u64(seed.wrapping_add(worker as u64));
+
+        loop {
+            let solution = get_local_descent(map, get_randomized_greedy(map, alpha, &mut rng), deadline);
+
+            let mut candidates = vec![solution];
+
+            if path_relinking {
+                let guide = {
+                    let elite_guard = elite.lock().unwrap();
+                    if elite_guard.is_empty() {
+                        None
+                    } else {
+                        Some(elite_guard[rng.gen_range(0..elite_guard.len())].clone())
+                    }
+                };
+
+                if let Some(relinked) = guide.and_then(|guide| path_relink(map, &candidates[0], &guide)) {
+                    candidates.push(get_local_descent(map, relinked, deadline));
+                }
+            }
+
+            for candidate in candidates {
+                update_elite(&elite, &candidate);
+
+                let mut best_guard = best_solution.lock().unwrap();
+                if candidate.distance < best_guard.distance {
+                    *best_guard = candidate;
+                }
+            }
+
+            if Instant::now() >= deadline {
+                break;
+            }
+        }
+    });
+
+    best_solution.into_inner().unwrap()
+}
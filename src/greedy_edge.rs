@@ -0,0 +1,71 @@
+use crate::distance_map::DistanceMap;
+use crate::priority_queue_structs::DisjointSet;
+use crate::solution::Solution;
+use crate::get_solution_length;
+
+/* Greedy edge construction, also known as greedy matching. Edges are added shortest first
+*  unless they would give a node a third edge or close a cycle early, which leaves a single
+*  path through every node. Joining its two ends gives the tour. */
+pub fn get_greedy_edge(map: &DistanceMap) -> Solution {
+    let count = map.point_count();
+
+    if count < 4 {
+        let route: Vec<u32> = (0..count as u32).collect();
+        let distance = get_solution_length(map, &route).0;
+        return Solution { route, distance };
+    }
+
+    let mut neighbours: Vec<Vec<u32>> = vec![Vec::with_capacity(2); count];
+    let mut disjoint_set = DisjointSet::new(count);
+    let mut added = 0;
+
+    for edge in map.get_sorted_edges() {
+        let (node1, node2) = (edge.node1, edge.node2);
+
+        if neighbours[node1 as usize].len() == 2 || neighbours[node2 as usize].len() == 2 {
+            continue;
+        }
+
+        if disjoint_set.find(node1) == disjoint_set.find(node2) {
+            continue;
+        }
+
+        disjoint_set.union(node1, node2);
+        neighbours[node1 as usize].push(node2);
+        neighbours[node2 as usize].push(node1);
+        added += 1;
+
+        if added == count - 1 {
+            break;
+        }
+    }
+
+    // Walk the path from one of its ends.
+    let start = (0..count as u32).find(|&node| neighbours[node as usize].len() == 1).unwrap();
+    let route = get_path_route(&neighbours, start, count);
+
+    let distance = get_solution_length(map, &route).0;
+
+    Solution { route, distance }
+}
+
+/* Follow a path of `length` nodes, given as each node's neighbours on it, from one of its ends. */
+pub fn get_path_route(neighbours: &[Vec<u32>], start: u32, length: usize) -> Vec<u32> {
+    let mut route: Vec<u32> = Vec::with_capacity(length);
+    let mut previous = start;
+    let mut current = start;
+
+    loop {
+        route.push(current);
+
+        match neighbours[current as usize].iter().find(|&&next| next != previous) {
+            Some(&next) if route.len() < length => {
+                previous = current;
+                current = next;
+            }
+            _ => break,
+        }
+    }
+
+    route
+}
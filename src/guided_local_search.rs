@@ -0,0 +1,114 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::distance_map::{DistanceMap, EdgeCost};
+use crate::solution::Solution;
+use crate::{activate_cities, get_solution_length, local_descent};
+
+/* Scales the penalty weight relative to the average edge length of the first local minima.
+*  Values between 0.1 and 0.5 are the usual recommendation for the TSP. */
+const GLS_ALPHA: f64 = 0.3;
+
+/* Distance map with guided local search penalties added on top. The local search moves only
+*  see this augmented cost, while solutions are still judged on the true distance. */
+pub struct PenalizedMap<'a> {
+    map: &'a DistanceMap,
+    penalties: HashMap<(u32, u32), u32>,
+    lambda: f64,
+}
+
+impl<'a> PenalizedMap<'a> {
+    pub fn new(map: &'a DistanceMap, lambda: f64) -> PenalizedMap<'a> {
+        PenalizedMap { map, penalties: HashMap::new(), lambda }
+    }
+
+    fn key(point1: &u32, point2: &u32) -> (u32, u32) {
+        if point1 < point2 { (*point1, *point2) } else { (*point2, *point1) }
+    }
+
+    pub fn get_penalty(&self, point1: &u32, point2: &u32) -> u32 {
+        *self.penalties.get(&PenalizedMap::key(point1, point2)).unwrap_or(&0)
+    }
+
+    pub fn penalize(&mut self, point1: &u32, point2: &u32) {
+        *self.penalties.entry(PenalizedMap::key(point1, point2)).or_insert(0) += 1;
+    }
+}
+
+impl EdgeCost for PenalizedMap<'_> {
+    fn get_distance_from_points(&self, point1: &u32, point2: &u32) -> f64 {
+        self.map.get_distance_from_points(point1, point2) + self.lambda * self.get_penalty(point1, point2) as f64
+    }
+}
+
+/* Penalize the tour edges with the highest utility, distance / (1 + penalty), and return
+*  their endpoints so the local search can take another look at them. */
+fn penalize_edges(cost: &mut PenalizedMap, route: &[u32]) -> Vec<u32> {
+    let count = route.len();
+
+    let utilities: Vec<f64> = (0..count)
+        .map(|i| {
+            let (node1, node2) = (route[i], route[(i + 1) % count]);
+            cost.map.get_distance_from_points(&node1, &node2) / (1.0 + cost.get_penalty(&node1, &node2) as f64)
+        })
+        .collect();
+
+    let max_utility = utilities.iter().cloned().fold(f64::MIN, f64::max);
+    let mut endpoints = Vec::new();
+
+    for (i, utility) in utilities.iter().enumerate() {
+        if *utility >= max_utility {
+            let (node1, node2) = (route[i], route[(i + 1) % count]);
+            cost.penalize(&node1, &node2);
+            endpoints.push(node1);
+            endpoints.push(node2);
+        }
+    }
+
+    endpoints
+}
+
+/* Guided local search. Each time the 2-opt/Or-opt descent settles into a local minima of the
+*  augmented cost, the longest, least penalized edges of that tour are penalized and the descent
+*  resumes from their endpoints. Runs until `max_duration` has passed and returns the tour with
+*  the shortest true length seen along the way. */
+pub fn get_guided_local_search(map: &DistanceMap, solution_input: Solution, max_duration: Duration) -> Solution {
+    let deadline = Instant::now() + max_duration;
+    let count = solution_input.len();
+
+    let mut best_solution = solution_input.clone();
+    best_solution.distance = get_solution_length(map, &best_solution.route).0;
+
+    if count < 5 {
+        return best_solution;
+    }
+
+    let mut current = solution_input;
+    let mut active: VecDeque<u32> = VecDeque::new();
+    let mut queued: Vec<bool> = vec![false; count];
+    activate_cities(&current.route, &mut active, &mut queued);
+
+    // Descend to a true local minima first so lambda is scaled from a sensible tour.
+    local_descent(map, &mut current, &mut active, &mut queued, deadline);
+    current.distance = get_solution_length(map, &current.route).0;
+    if current.distance < best_solution.distance {
+        best_solution = current.clone();
+    }
+
+    let mut cost = PenalizedMap::new(map, GLS_ALPHA * current.distance / count as f64);
+
+    while Instant::now() < deadline {
+        let endpoints = penalize_edges(&mut cost, &current.route);
+        activate_cities(&endpoints, &mut active, &mut queued);
+
+        local_descent(&cost, &mut current, &mut active, &mut queued, deadline);
+
+        let length = get_solution_length(map, &current.route).0;
+        if length < best_solution.distance {
+            best_solution.route = current.route.clone();
+            best_solution.distance = length;
+        }
+    }
+
+    best_solution
+}
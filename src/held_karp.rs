@@ -0,0 +1,261 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering as AtomicOrdering};
+
+use rayon::prelude::*;
+
+use crate::checkpoint::{write_atomically, Checkpointer, Frontier};
+use crate::distance_map::DistanceMap;
+use crate::search_limits::SearchStats;
+use crate::solution::Solution;
+use crate::get_solution_length;
+
+/* Bytes per DP entry: the f64 cost of the best path plus the u8 index of its predecessor. */
+const BYTES_PER_ENTRY: u64 = 9;
+
+/* Largest instance the bitmask indexing supports, regardless of memory. */
+pub const MAX_HELD_KARP_POINTS: usize = 32;
+
+/* Returns the number of bytes the Held-Karp table needs for the given number of points.
+*  Node zero is fixed as the start, so the table holds 2^(n-1) subsets of n-1 end points. */
+pub fn estimate_held_karp_memory(point_count: usize) -> u64 {
+    if point_count < 2 {
+        return 0;
+    }
+
+    let others = (point_count - 1) as u32;
+
+    2u64.saturating_pow(others).saturating_mul(others as u64).saturating_mul(BYTES_PER_ENTRY)
+}
+
+/* Returns true if the instance fits under the memory limit, in bytes. */
+pub fn is_held_karp_feasible(point_count: usize, memory_limit: u64) -> bool {
+    point_count <= MAX_HELD_KARP_POINTS && estimate_held_karp_memory(point_count) <= memory_limit
+}
+
+/* Every subset of `bits` bits with exactly `size` bits set, in increasing order. */
+fn get_subsets(bits: usize, size: usize) -> Vec<usize> {
+    let mut subsets = Vec::new();
+
+    if size == 0 || size > bits {
+        return subsets;
+    }
+
+    // Gosper's hack: step to the next larger integer with the same number of set bits.
+    let mut subset: usize = (1 << size) - 1;
+    while subset < (1 << bits) {
+        subsets.push(subset);
+
+        let lowest = subset & subset.wrapping_neg();
+        let ripple = subset + lowest;
+        subset = (((ripple ^ subset) >> 2) / lowest) | ripple;
+    }
+
+    subsets
+}
+
+/* Write the whole table in binary, every cost as little endian f64 bits followed by every
+*  parent. */
+fn save_table(filename: &str, cost: &[AtomicU64], parent: &[AtomicU8]) -> io::Result<()> {
+    write_atomically(filename, |writer| {
+        for entry in cost.iter() {
+            writer.write_all(&entry.load(AtomicOrdering::Relaxed).to_le_bytes())?;
+        }
+
+        let parents: Vec<u8> = parent.iter().map(|entry| entry.load(AtomicOrdering::Relaxed)).collect();
+        writer.write_all(&parents)
+    })
+}
+
+fn load_table(filename: &str, cost: &[AtomicU64], parent: &[AtomicU8]) -> io::Result<()> {
+    let file = File::open(filename)?;
+
+    if file.metadata()?.len() != cost.len() as u64 * BYTES_PER_ENTRY {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "the table does not match the instance"));
+    }
+
+    let mut reader = BufReader::new(file);
+    let mut bytes = [0u8; 8];
+
+    for entry in cost.iter() {
+        reader.read_exact(&mut bytes)?;
+        entry.store(u64::from_le_bytes(bytes), AtomicOrdering::Relaxed);
+    }
+
+    for entry in parent.iter() {
+        reader.read_exact(&mut bytes[..1])?;
+        entry.store(bytes[0], AtomicOrdering::Relaxed);
+    }
+
+    Ok(())
+}
+
+/* Save the table first, so the checkpoint never points at layers the table doesn't hold. */
+fn save_checkpoint(checkpointer: &Checkpointer, incumbent: &Solution, stats: &SearchStats,
+    cost: &[AtomicU64], parent: &[AtomicU8], completed_layers: usize) {
+    let table_filename = checkpointer.get_table_filename();
+
+    match save_table(&table_filename, cost, parent) {
+        Ok(()) => checkpointer.save(incumbent, stats, Frontier::HeldKarpLayers(completed_layers)),
+        Err(error) => println!("Failed to save the Held-Karp table to {}: {}", table_filename, error),
+    }
+}
+
+/* Table of the shortest paths from node zero through subsets of nodes 1..=others. Bit j of a
+*  subset stands for node j + 1, and the subsets of a single node start out filled in. */
+fn new_table(matrix: &[Vec<f64>], others: usize) -> (Vec<AtomicU64>, Vec<AtomicU8>) {
+    let subset_count: usize = 1 << others;
+
+    let cost: Vec<AtomicU64> = (0..subset_count * others).into_par_iter()
+        .map(|_| AtomicU64::new(f64::INFINITY.to_bits()))
+        .collect();
+    let parent: Vec<AtomicU8> = (0..subset_count * others).into_par_iter()
+        .map(|_| AtomicU8::new(u8::MAX))
+        .collect();
+
+    for j in 0..others {
+        cost[(1 << j) * others + j].store(matrix[0][j + 1].to_bits(), AtomicOrdering::Relaxed);
+    }
+
+    (cost, parent)
+}
+
+/* Fill in one layer of subsets of the same size. It only reads the layer below, so the subsets
+*  are evaluated in parallel. */
+fn fill_layer(matrix: &[Vec<f64>], others: usize, subsets: &[usize], cost: &[AtomicU64], parent: &[AtomicU8]) {
+    subsets.par_iter().for_each(|&subset| {
+        for j in (0..others).filter(|j| subset & (1 << j) != 0) {
+            let previous = subset ^ (1 << j);
+            let mut best = f64::INFINITY;
+            let mut best_parent = u8::MAX;
+
+            for i in (0..others).filter(|i| previous & (1 << i) != 0) {
+                let candidate = f64::from_bits(cost[previous * others + i].load(AtomicOrdering::Relaxed)) + matrix[i + 1][j + 1];
+
+                if candidate < best {
+                    best = candidate;
+                    best_parent = i as u8;
+                }
+            }
+
+            cost[subset * others + j].store(best.to_bits(), AtomicOrdering::Relaxed);
+            parent[subset * others + j].store(best_parent, AtomicOrdering::Relaxed);
+        }
+    });
+}
+
+/* Shortest path through every node of the full table that continues on to `end`. Returns it
+*  from node zero, without `end`. */
+fn get_table_path(matrix: &[Vec<f64>], others: usize, cost: &[AtomicU64], parent: &[AtomicU8], end: usize) -> Vec<usize> {
+    let full = (1 << others) - 1;
+    let (mut last, _) = (0..others)
+        .map(|j| (j, f64::from_bits(cost[full * others + j].load(AtomicOrdering::Relaxed)) + matrix[j + 1][end]))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .unwrap();
+
+    // Walk the parents back from the full subset.
+    let mut path: Vec<usize> = Vec::with_capacity(others + 1);
+    let mut subset = full;
+    loop {
+        path.push(last + 1);
+        let previous = parent[subset * others + last].load(AtomicOrdering::Relaxed);
+        subset ^= 1 << last;
+
+        if subset == 0 {
+            break;
+        }
+        last = previous as usize;
+    }
+
+    path.push(0);
+    path.reverse();
+    path
+}
+
+/* Bitmask dynamic programming solver. cost[S][j] is the shortest path that starts at node zero,
+*  visits exactly the nodes in S and ends at j. Every layer of subsets with the same size only
+*  reads the layer below it, so each layer is evaluated in parallel.
+*
+*  Runs in O(2^n n^2) time and O(2^n n) memory. Check `is_held_karp_feasible` first. Every
+*  subset counts as a search node, and the limits are checked between layers. Returns None if
+*  they ran out before the table was complete.
+*
+*  With a checkpointer the table is saved between layers, along with `incumbent` in case the
+*  search is stopped. A search resumed with `completed_layers` reloads the table and carries on
+*  from the next layer. */
+pub fn get_held_karp(map: &DistanceMap, stats: &SearchStats, checkpointer: Option<&Checkpointer>,
+    incumbent: &Solution, completed_layers: usize) -> Option<Solution> {
+    let count = map.point_count();
+
+    if count <= 3 {
+        let route: Vec<u32> = (0..count as u32).collect();
+        let distance = get_solution_length(map, &route).0;
+        return Some(Solution { route, distance });
+    }
+
+    let matrix = map.get_matrix();
+    let others = count - 1;
+    let (cost, parent) = new_table(&matrix, others);
+
+    let mut first_size = 2;
+
+    if let Some(checkpointer) = checkpointer.filter(|_| completed_layers >= first_size) {
+        match load_table(&checkpointer.get_table_filename(), &cost, &parent) {
+            Ok(()) => first_size = completed_layers + 1,
+            Err(error) => println!("Failed to load the Held-Karp table, starting over: {}", error),
+        }
+    }
+
+    for size in first_size..=others {
+        if let Some(checkpointer) = checkpointer.filter(|checkpointer| checkpointer.is_due()) {
+            save_checkpoint(checkpointer, incumbent, stats, &cost, &parent, size - 1);
+        }
+
+        let subsets = get_subsets(others, size);
+        if !stats.visit_many(subsets.len() as u64) {
+            if let Some(checkpointer) = checkpointer {
+                save_checkpoint(checkpointer, incumbent, stats, &cost, &parent, size - 1);
+            }
+            return None;
+        }
+
+        fill_layer(&matrix, others, &subsets, &cost, &parent);
+    }
+
+    // Close the tour back to node zero.
+    let route: Vec<u32> = get_table_path(&matrix, others, &cost, &parent, 0).into_iter()
+        .map(|node| node as u32)
+        .collect();
+    let distance = get_solution_length(map, &route).0;
+
+    Some(Solution { route, distance })
+}
+
+/* Held-Karp for the shortest path from the first node of `matrix` to its last, through every
+*  node in between. Takes the memory `estimate_held_karp_memory` gives for one node less, and
+*  the same limits as the tour. Returns the order of the nodes, or None if the limits ran out. */
+pub fn get_held_karp_path(matrix: &[Vec<f64>], stats: &SearchStats) -> Option<Vec<usize>> {
+    let count = matrix.len();
+
+    if count <= 3 {
+        return Some((0..count).collect());
+    }
+
+    let last = count - 1;
+    let others = count - 2;
+    let (cost, parent) = new_table(matrix, others);
+
+    for size in 2..=others {
+        let subsets = get_subsets(others, size);
+        if !stats.visit_many(subsets.len() as u64) {
+            return None;
+        }
+
+        fill_layer(matrix, others, &subsets, &cost, &parent);
+    }
+
+    let mut path = get_table_path(matrix, others, &cost, &parent, last);
+    path.push(last);
+
+    Some(path)
+}
@@ -10,6 +10,61 @@ fn print_local_options() {
     println!("Desired runtime in seconds");
     print!("                     ");
     println!("Example: ./tsp solve_local points10.json 60");
+    print!("--method <name>      ");
+    println!("Local search to run: 'two_opt' (default), 'gls' (guided local search), 'grasp',");
+    print!("                     ");
+    println!("'multi_start' (parallel restarts on every core), 'decomposition' (solve cells of");
+    print!("                     ");
+    println!("the plane separately and join them, used automatically above 20000 points),");
+    print!("                     ");
+    println!("'multilevel' (coarsen by fixing short edges, then refine level by level)");
+    print!("                     ");
+    println!("or 'portfolio' (race several of these in parallel, sharing the best tour)");
+    print!("--solvers <names>    ");
+    println!("Comma separated methods 'portfolio' races (default gls,multi_start,grasp,multilevel)");
+    print!("--rounds <count>     ");
+    println!("Rounds 'portfolio' splits its time into. The solvers only pick up each other's best");
+    print!("                     ");
+    println!("tour at the start of a round (default 4)");
+    print!("--cell <points>      ");
+    println!("Largest cell 'decomposition' solves on its own (default 200)");
+    print!("--alpha <0.0-1.0>    ");
+    println!("Greediness of the GRASP construction, 0.0 is pure nearest neighbor (default 0.2)");
+    print!("--relink             ");
+    println!("Path relink GRASP tours with the elite tours found so far");
+    print!("--merge              ");
+    println!("Merge the best 'two_opt' restarts into the shortest tour using only their edges");
+    print!("--window <4-16>      ");
+    println!("Finish by reordering every run of this many consecutive cities exactly");
+    print!("--islands            ");
+    println!("Run multi_start as an island model that migrates each island's best tour");
+    print!("--gap                ");
+    println!("Report the Held-Karp lower bound and the gap of the solution found");
+    print!("--path               ");
+    println!("Find the shortest path through every point instead of a closed tour");
+    print!("--from <node>        ");
+    println!("Point the path has to start at, implies --path");
+    print!("--to <node>          ");
+    println!("Point the path has to end at, implies --path");
+    print!("--seed <number>      ");
+    println!("Seed for the per-thread random number generators");
+    println!("--construction <name>");
+    print!("                     ");
+    println!("Starting tour: 'nearest_neighbor' (default), 'greedy_edge', 'christofides'");
+    print!("                     ");
+    println!("'nearest_insertion', 'farthest_insertion', 'cheapest_insertion', 'random_insertion'");
+    print!("                     ");
+    println!("'convex_hull', 'hilbert', 'sierpinski' (space-filling curves, for huge instances)");
+    print!("                     ");
+    println!("'savings' (Clarke-Wright) or 'multi_start_nn' (nearest neighbor from every city)");
+    print!("--depot <node>       ");
+    println!("Depot of the savings construction (default: the point closest to the centroid)");
+    print!("--starts <count>     ");
+    println!("Cities 'multi_start_nn' starts from, sampled at random (default: every city)");
+    print!("--pool <count>       ");
+    println!("Best 'multi_start_nn' tours to run 2-opt on, keeping the shortest result (default 1)");
+    print!("                     ");
+    println!("Example: ./tsp solve_local points10.json 60 --method gls");
     println!("");
 }
 
@@ -19,9 +74,68 @@ fn print_optimal_options() {
     println!("Path to the .json file containing the points to solve");
     print!("                     ");
     println!("Example: ./tsp solve_optimal points10.json");
+    print!("--method <name>      ");
+    println!("Exact solver to run: 'held_karp' (default), 'branch_and_bound', 'best_first'");
+    print!("                     ");
+    println!("'little' (reduced cost matrix branch and bound) or 'cutting_plane' (LP branch and cut)");
+    print!("--memory <MiB>       ");
+    println!("Memory the Held-Karp table or best-first queue may use (default 4096)");
+    print!("                     ");
+    println!("Held-Karp falls back to branch and bound, best-first to depth-first search");
+    print!("--time <seconds>     ");
+    println!("Stop the search after this long and report the best solution and bound so far");
+    println!("--construction <name>");
+    print!("                     ");
+    println!("Tour the starting bound comes from: 'nearest_neighbor' (default), 'greedy_edge'");
+    print!("                     ");
+    println!("'christofides', 'nearest_insertion', 'farthest_insertion', 'cheapest_insertion'");
+    print!("                     ");
+    println!("'random_insertion', 'convex_hull', 'hilbert', 'sierpinski', 'savings'");
+    print!("                     ");
+    println!("or 'multi_start_nn'");
+    print!("--depot <node>       ");
+    println!("Depot of the savings construction (default: the point closest to the centroid)");
+    print!("--starts <count>     ");
+    println!("Cities 'multi_start_nn' starts from, sampled at random (default: every city)");
+    print!("--pool <count>       ");
+    println!("Best 'multi_start_nn' tours to run 2-opt on, keeping the shortest result (default 1)");
+    print!("--nodes <count>      ");
+    println!("Stop the search after exploring this many nodes in total");
+    print!("--path               ");
+    println!("Find the shortest path through every point instead of a closed tour");
+    print!("--from <node>        ");
+    println!("Point the path has to start at, implies --path");
+    print!("--to <node>          ");
+    println!("Point the path has to end at, implies --path");
+    print!("--checkpoint <file>  ");
+    println!("Save the search to this file every minute and when it stops, to continue with 'resume'");
+    print!("                     ");
+    println!("Supported by 'held_karp', 'branch_and_bound' and 'best_first'");
+    println!();
+}
+
+fn print_resume_options() {
+    println!("Options for 'resume':");
+    print!("<checkpoint>         ");
+    println!("Checkpoint file written by 'solve_optimal --checkpoint'");
+    print!("                     ");
+    println!("Takes the --time, --nodes and --memory options of 'solve_optimal'");
+    print!("                     ");
+    println!("Example: ./tsp resume points20.checkpoint.json --time 3600");
     println!("");
 }
 
+fn print_bound_options() {
+    println!("Options for 'bound':");
+    print!("<file>               ");
+    println!("Path to the .json file containing the points to bound");
+    print!("[solution]           ");
+    println!("Optional solution .json file to report the optimality gap of");
+    print!("                     ");
+    println!("Example: ./tsp bound points100.json points100_local_solution.json");
+    println!();
+}
+
 fn print_generate_options() {
     println!("Options for 'generate':");
     print!("<number>             ");
@@ -41,6 +155,12 @@ fn print_usage() {
     println!("Find the optimal solution");
     print!("solve_local          ");
     println!("Find a local minima solution.");
+    print!("bound                ");
+    println!("Compute the Held-Karp lower bound on the optimal solution.");
+    print!("resume               ");
+    println!("Continue an exact search from a checkpoint.");
+    print!("auto                 ");
+    println!("Pick a solver from the size and layout of the points.");
     println!("");
 }
 
@@ -55,6 +175,8 @@ pub fn print_help() {
     print_generate_options();
     print_optimal_options();
     print_local_options();
+    print_bound_options();
+    print_resume_options();
     print_general_options();
 }
 
@@ -142,6 +264,9 @@ pub fn get_usage(args: &Vec<String>,) -> query::Usage {
             "generate" | "generate_problem" => return query::Usage::Generate,
             "solve_optimal" | "optimal" => return query::Usage::SolveOptimal,
             "solve_local" | "local" => return query::Usage::SolveLocal,
+            "bound" | "lower_bound" => return query::Usage::Bound,
+            "resume" => return query::Usage::Resume,
+            "auto" => return query::Usage::Auto,
             _ => { 
                 println!("Invalid usage parameter."); 
                 return query_usage(); }
@@ -285,10 +410,228 @@ fn get_execution_time(query: &mut query::UserQuery, args: &Vec<String>) {
     
 }
 
+fn get_optimal_method(name: &str) -> Option<query::OptimalMethod> {
+    match name.to_lowercase().as_str() {
+        "held_karp" | "heldkarp" | "dp" | "dynamic" => Some(query::OptimalMethod::HeldKarp),
+        "branch_and_bound" | "bnb" => Some(query::OptimalMethod::BranchAndBound),
+        "best_first" | "priority_queue" => Some(query::OptimalMethod::BestFirst),
+        "little" | "reduced_matrix" => Some(query::OptimalMethod::ReducedMatrix),
+        "cutting_plane" | "branch_and_cut" => Some(query::OptimalMethod::CuttingPlane),
+        _ => None,
+    }
+}
+
+fn get_construction(name: &str) -> Option<query::Construction> {
+    match name.to_lowercase().as_str() {
+        "nearest_neighbor" | "nearest_neighbour" | "nn" | "greedy" => Some(query::Construction::NearestNeighbor),
+        "christofides" => Some(query::Construction::Christofides),
+        "greedy_edge" | "greedy_matching" => Some(query::Construction::GreedyEdge),
+        "nearest_insertion" => Some(query::Construction::NearestInsertion),
+        "farthest_insertion" => Some(query::Construction::FarthestInsertion),
+        "cheapest_insertion" => Some(query::Construction::CheapestInsertion),
+        "random_insertion" => Some(query::Construction::RandomInsertion),
+        "convex_hull" | "hull_insertion" | "convex_hull_insertion" => Some(query::Construction::ConvexHullInsertion),
+        "hilbert" | "hilbert_curve" => Some(query::Construction::Hilbert),
+        "sierpinski" | "sierpinski_curve" => Some(query::Construction::Sierpinski),
+        "savings" | "clarke_wright" => Some(query::Construction::Savings),
+        "multi_start_nn" | "multi_nn" | "all_nearest_neighbor" => Some(query::Construction::MultiStartNearestNeighbor),
+        _ => None,
+    }
+}
+
+fn get_local_method(name: &str) -> Option<query::LocalMethod> {
+    match name.to_lowercase().as_str() {
+        "two_opt" | "2opt" | "2-opt" => Some(query::LocalMethod::TwoOpt),
+        "gls" | "guided" | "guided_local_search" => Some(query::LocalMethod::GuidedLocalSearch),
+        "grasp" => Some(query::LocalMethod::Grasp),
+        "multi_start" | "multistart" => Some(query::LocalMethod::MultiStart),
+        "decomposition" | "karp" => Some(query::LocalMethod::Decomposition),
+        "multilevel" | "coarsening" => Some(query::LocalMethod::Multilevel),
+        "portfolio" => Some(query::LocalMethod::Portfolio),
+        _ => None,
+    }
+}
+
+/* Options are given as '--name value' pairs after the positional arguments. */
+fn parse_options(query: &mut query::UserQuery, args: &[String]) {
+    let mut i = 2;
+
+    while i < args.len() {
+        let value = args.get(i + 1).map(|value| value.as_str()).unwrap_or("");
+
+        match args[i].to_lowercase().as_str() {
+            "--method" | "-m" => {
+                if query.usage == query::Usage::SolveOptimal {
+                    match get_optimal_method(value) {
+                        Some(method) => query.optimal_method = method,
+                        None => println!("Invalid method '{}'. Using the default.", value),
+                    }
+                } else {
+                    match get_local_method(value) {
+                        Some(method) => query.local_method = method,
+                        None => println!("Invalid method '{}'. Using the default.", value),
+                    }
+                }
+                i += 1;
+            }
+            "--memory" => {
+                match value.parse::<u64>() {
+                    Ok(mebibytes) if mebibytes > 0 => query.memory_limit = mebibytes * 1024 * 1024,
+                    _ => println!("Invalid memory limit '{}'. Using the default.", value),
+                }
+                i += 1;
+            }
+            "--alpha" => {
+                match value.parse::<f64>() {
+                    Ok(alpha) if (0.0..=1.0).contains(&alpha) => query.grasp_alpha = alpha,
+                    _ => println!("Invalid alpha '{}'. Using the default.", value),
+                }
+                i += 1;
+            }
+            "--construction" => {
+                match get_construction(value) {
+                    Some(construction) => query.construction = construction,
+                    None => println!("Invalid construction '{}'. Using the default.", value),
+                }
+                i += 1;
+            }
+            "--depot" => {
+                match value.parse::<u32>() {
+                    Ok(node) => query.depot = Some(node),
+                    Err(_) => println!("Invalid depot '{}'. Using the default.", value),
+                }
+                i += 1;
+            }
+            "--starts" => {
+                match value.parse::<usize>() {
+                    Ok(starts) if starts > 0 => query.starts = Some(starts),
+                    _ => println!("Invalid start count '{}'. Starting from every city.", value),
+                }
+                i += 1;
+            }
+            "--pool" => {
+                match value.parse::<usize>() {
+                    Ok(size) if size > 0 => query.pool_size = size,
+                    _ => println!("Invalid pool size '{}'. Using the default.", value),
+                }
+                i += 1;
+            }
+            "--cell" => {
+                match value.parse::<usize>() {
+                    Ok(size) if size > 0 => query.cell_size = size,
+                    _ => println!("Invalid cell size '{}'. Using the default.", value),
+                }
+                i += 1;
+            }
+            "--window" => {
+                match value.parse::<usize>() {
+                    Ok(size) if (4..=16).contains(&size) => query.window_size = Some(size),
+                    _ => println!("Invalid window size '{}'. It must be between 4 and 16.", value),
+                }
+                i += 1;
+            }
+            "--solvers" => {
+                let solvers: Option<Vec<query::LocalMethod>> = value.split(',').map(|name| match get_local_method(name.trim()) {
+                    Some(query::LocalMethod::Decomposition | query::LocalMethod::Portfolio) | None => None,
+                    method => method,
+                }).collect();
+
+                match solvers {
+                    Some(solvers) if !solvers.is_empty() => query.portfolio = solvers,
+                    _ => println!("Invalid solvers '{}'. Using the default portfolio.", value),
+                }
+                i += 1;
+            }
+            "--rounds" => {
+                match value.parse::<u32>() {
+                    Ok(rounds) if rounds > 0 => query.portfolio_rounds = rounds,
+                    _ => println!("Invalid round count '{}'. Using the default.", value),
+                }
+                i += 1;
+            }
+            "--from" => {
+                match value.parse::<u32>() {
+                    Ok(node) => {
+                        query.open_path = true;
+                        query.path_start = Some(node);
+                    }
+                    Err(_) => println!("Invalid start '{}'. Leaving the start free.", value),
+                }
+                i += 1;
+            }
+            "--to" => {
+                match value.parse::<u32>() {
+                    Ok(node) => {
+                        query.open_path = true;
+                        query.path_end = Some(node);
+                    }
+                    Err(_) => println!("Invalid end '{}'. Leaving the end free.", value),
+                }
+                i += 1;
+            }
+            "--path" => query.open_path = true,
+            "--relink" => query.path_relinking = true,
+            "--merge" => query.merge_tours = true,
+            "--islands" => query.islands = true,
+            "--gap" => query.report_gap = true,
+            "--time" => {
+                match value.parse::<u64>() {
+                    Ok(seconds) => query.time_limit = Some(seconds),
+                    Err(_) => println!("Invalid time limit '{}'. Running without one.", value),
+                }
+                i += 1;
+            }
+            "--checkpoint" => {
+                if value.is_empty() {
+                    println!("Missing checkpoint file. Running without checkpoints.");
+                } else {
+                    query.checkpoint_filename = Some(value.to_string());
+                }
+                i += 1;
+            }
+            "--nodes" => {
+                match value.parse::<u64>() {
+                    Ok(nodes) => query.node_limit = Some(nodes),
+                    Err(_) => println!("Invalid node limit '{}'. Running without one.", value),
+                }
+                i += 1;
+            }
+            "--seed" => {
+                match value.parse::<u64>() {
+                    Ok(seed) => query.seed = Some(seed),
+                    Err(_) => println!("Invalid seed '{}'. Using a random seed.", value),
+                }
+                i += 1;
+            }
+            _ => {}
+        }
+
+        i += 1;
+    }
+}
+
 pub fn parse_solve(query: &mut query::UserQuery, args: &Vec<String>) {
     get_file_name(query, args);
 
-    if query.usage == query::Usage::SolveLocal {
+    if query.usage == query::Usage::SolveLocal || query.usage == query::Usage::Auto {
         get_execution_time(query, args);
     }
-}
\ No newline at end of file
+
+    if query.usage == query::Usage::Bound && args.len() > 3 && !args[3].starts_with("--") {
+        query.solution_filename = Some(args[3].clone());
+    }
+
+    parse_options(query, args);
+}
+
+pub fn parse_resume(query: &mut query::UserQuery, args: &[String]) {
+    if args.len() > 2 && File::open(&args[2]).is_ok() {
+        query.checkpoint_filename = Some(args[2].clone());
+    } else {
+        println!("Checkpoint file not found.");
+        print_resume_options();
+        std::process::exit(1);
+    }
+
+    parse_options(query, args);
+}
@@ -0,0 +1,242 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rayon::prelude::*;
+
+use crate::distance_map::DistanceMap;
+use crate::point::Points;
+use crate::solution::Solution;
+use crate::get_solution_length;
+
+/* Shortest slice rayon splits off, below which a scan isn't worth spreading over threads. */
+const PARALLEL_MIN_LENGTH: usize = 1024;
+
+/* Which node goes into the partial tour next. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InsertionRule {
+    /* The node closest to the tour. */
+    Nearest,
+    /* The node farthest from the tour, which sketches the outline of the instance first. */
+    Farthest,
+    /* Any node, in random order. */
+    Random,
+    /* The node that lengthens the tour the least. */
+    Cheapest,
+}
+
+/* Partial tour kept as a successor list, so a node is inserted in O(1). */
+struct PartialTour {
+    next: Vec<u32>,
+    nodes: Vec<u32>,
+}
+
+impl PartialTour {
+    /* Cycle through the seed nodes in order. */
+    fn new(count: usize, seed: &[u32]) -> PartialTour {
+        let mut next: Vec<u32> = vec![u32::MAX; count];
+        for (i, &node) in seed.iter().enumerate() {
+            next[node as usize] = seed[(i + 1) % seed.len()];
+        }
+
+        PartialTour { next, nodes: seed.to_vec() }
+    }
+
+    fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /* How much putting `node` between a and the node after it lengthens the tour. */
+    fn get_insertion_cost(&self, map: &DistanceMap, a: u32, node: u32) -> f64 {
+        let b = self.next[a as usize];
+
+        map.get_distance_from_points(&a, &node) + map.get_distance_from_points(&node, &b) - map.get_distance_from_points(&a, &b)
+    }
+
+    /* The node after which `node` is cheapest to insert, and what it costs. */
+    fn get_cheapest_edge(&self, map: &DistanceMap, node: u32) -> (u32, f64) {
+        self.nodes.par_iter()
+            .with_min_len(PARALLEL_MIN_LENGTH)
+            .map(|&a| (a, self.get_insertion_cost(map, a, node)))
+            .min_by(|x, y| x.1.partial_cmp(&y.1).unwrap())
+            .unwrap()
+    }
+
+    fn insert_after(&mut self, a: u32, node: u32) {
+        self.next[node as usize] = self.next[a as usize];
+        self.next[a as usize] = node;
+        self.nodes.push(node);
+    }
+
+    fn get_solution(&self, map: &DistanceMap) -> Solution {
+        let mut route: Vec<u32> = Vec::with_capacity(self.len());
+        let mut node = self.nodes[0];
+
+        while route.len() < self.len() {
+            route.push(node);
+            node = self.next[node as usize];
+        }
+
+        let distance = get_solution_length(map, &route).0;
+        Solution { route, distance }
+    }
+}
+
+/* Insert the remaining nodes in order of their distance to the tour, or at random. Every
+*  node goes wherever it lengthens the tour the least. */
+fn insert_by_distance(map: &DistanceMap, tour: &mut PartialTour, remaining_input: Vec<u32>, rule: InsertionRule, seed: u64) {
+    let mut remaining = remaining_input;
+
+    if rule == InsertionRule::Random {
+        remaining.shuffle(&mut StdRng::seed_from_u64(seed));
+
+        while let Some(node) = remaining.pop() {
+            let (a, _) = tour.get_cheapest_edge(map, node);
+            tour.insert_after(a, node);
+        }
+        return;
+    }
+
+    // Distance from every remaining node to the closest node of the tour.
+    let mut closest: Vec<f64> = remaining.par_iter()
+        .map(|&node| tour.nodes.iter().map(|other| map.get_distance_from_points(&node, other)).fold(f64::INFINITY, f64::min))
+        .collect();
+
+    while !remaining.is_empty() {
+        let candidates = closest.par_iter().with_min_len(PARALLEL_MIN_LENGTH).enumerate();
+        let (index, _) = match rule {
+            InsertionRule::Farthest => candidates.max_by(|x, y| x.1.partial_cmp(y.1).unwrap()).unwrap(),
+            _ => candidates.min_by(|x, y| x.1.partial_cmp(y.1).unwrap()).unwrap(),
+        };
+
+        let node = remaining.swap_remove(index);
+        closest.swap_remove(index);
+
+        let (a, _) = tour.get_cheapest_edge(map, node);
+        tour.insert_after(a, node);
+
+        remaining.par_iter().zip(closest.par_iter_mut()).with_min_len(PARALLEL_MIN_LENGTH).for_each(|(other, distance)| {
+            *distance = distance.min(map.get_distance_from_points(&node, other));
+        });
+    }
+}
+
+/* Insert whichever remaining node lengthens the tour the least. Every node remembers its
+*  cheapest edge, which only has to be searched for again when that edge is the one split. */
+fn insert_cheapest(map: &DistanceMap, tour: &mut PartialTour, remaining_input: Vec<u32>) {
+    let mut remaining = remaining_input;
+    let mut best: Vec<(u32, f64)> = remaining.par_iter().map(|&node| tour.get_cheapest_edge(map, node)).collect();
+
+    while !remaining.is_empty() {
+        let (index, _) = best.par_iter()
+            .with_min_len(PARALLEL_MIN_LENGTH)
+            .enumerate()
+            .min_by(|x, y| x.1.1.partial_cmp(&y.1.1).unwrap())
+            .unwrap();
+
+        let node = remaining.swap_remove(index);
+        let (a, _) = best.swap_remove(index);
+        tour.insert_after(a, node);
+
+        let tour_ref = &*tour;
+        remaining.par_iter().zip(best.par_iter_mut()).for_each(|(&other, (edge, cost))| {
+            if *edge == a {
+                (*edge, *cost) = tour_ref.get_cheapest_edge(map, other);
+                return;
+            }
+
+            for new_edge in [a, node] {
+                let new_cost = tour_ref.get_insertion_cost(map, new_edge, other);
+                if new_cost < *cost {
+                    (*edge, *cost) = (new_edge, new_cost);
+                }
+            }
+        });
+    }
+}
+
+/* Insertion construction. The tour starts from node zero and whichever node the rule picks
+*  next, then grows one node at a time. */
+pub fn get_insertion(map: &DistanceMap, rule: InsertionRule, seed: u64) -> Solution {
+    let count = map.point_count();
+
+    if count < 4 {
+        let route: Vec<u32> = (0..count as u32).collect();
+        let distance = get_solution_length(map, &route).0;
+        return Solution { route, distance };
+    }
+
+    let distances: Vec<f64> = (0..count as u32).map(|node| map.get_distance_from_points(&0, &node)).collect();
+    let others = 1..count as u32;
+    let second = match rule {
+        InsertionRule::Farthest => others.max_by(|&x, &y| distances[x as usize].partial_cmp(&distances[y as usize]).unwrap()),
+        _ => others.min_by(|&x, &y| distances[x as usize].partial_cmp(&distances[y as usize]).unwrap()),
+    }.unwrap();
+
+    let mut tour = PartialTour::new(count, &[0, second]);
+    let remaining: Vec<u32> = (1..count as u32).filter(|&node| node != second).collect();
+
+    match rule {
+        InsertionRule::Cheapest => insert_cheapest(map, &mut tour, remaining),
+        _ => insert_by_distance(map, &mut tour, remaining, rule, seed),
+    }
+
+    tour.get_solution(map)
+}
+
+/* Andrew's monotone chain. Returns the indices of the hull's corners in counterclockwise
+*  order, leaving out points that lie on its edges. */
+pub fn get_convex_hull(points: &Points) -> Vec<u32> {
+    let mut order: Vec<u32> = (0..points.points.len() as u32).collect();
+    order.par_sort_unstable_by(|&a, &b| {
+        let (a, b) = (&points.points[a as usize], &points.points[b as usize]);
+        a.x.partial_cmp(&b.x).unwrap().then(a.y.partial_cmp(&b.y).unwrap())
+    });
+
+    let cross = |o: u32, a: u32, b: u32| -> f64 {
+        let (o, a, b) = (&points.points[o as usize], &points.points[a as usize], &points.points[b as usize]);
+        (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+    };
+
+    let mut hull: Vec<u32> = Vec::new();
+
+    // Lower hull left to right, then upper hull right to left.
+    for pass in [order.clone(), order.into_iter().rev().collect()] {
+        let start = hull.len();
+
+        for node in pass {
+            while hull.len() >= start + 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], node) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(node);
+        }
+
+        // The last point of each chain starts the other one.
+        hull.pop();
+    }
+
+    hull
+}
+
+/* Convex hull insertion. Every tour that doesn't cross itself visits the hull's corners in
+*  hull order, so the hull is a safe outline to start from. The other nodes are then added by
+*  cheapest insertion. */
+pub fn get_convex_hull_insertion(map: &DistanceMap, points: &Points) -> Solution {
+    let count = map.point_count();
+    let hull = get_convex_hull(points);
+
+    if count < 4 || hull.len() < 3 {
+        return get_insertion(map, InsertionRule::Cheapest, 0);
+    }
+
+    let mut on_hull: Vec<bool> = vec![false; count];
+    for &node in hull.iter() {
+        on_hull[node as usize] = true;
+    }
+
+    let mut tour = PartialTour::new(count, &hull);
+    let remaining: Vec<u32> = (0..count as u32).filter(|&node| !on_hull[node as usize]).collect();
+
+    insert_cheapest(map, &mut tour, remaining);
+
+    tour.get_solution(map)
+}
@@ -3,11 +3,12 @@ use std::fs::File;
 use std::env;
 use std::io::Read;
 use std::io;
-use std::collections::{HashSet, BinaryHeap};
+use std::collections::{HashSet, BinaryHeap, VecDeque};
 use std::time::Instant;
 use std::time::Duration;
 use rand::seq::SliceRandom;
-use rand::thread_rng; 
+use rand::thread_rng;
+use rand::Rng;
 
 use std::sync::{Arc, Mutex,};
 use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
@@ -18,22 +19,118 @@ mod point;
 use crate::point::Points;
 
 mod solution;
-use crate::solution::Solution;
+use crate::solution::{Incumbent, ReportedSolution, SearchReport, Solution};
 
 mod distance_map;
 use crate::distance_map::DistanceMap;
+use crate::distance_map::EdgeCost;
 
 mod priority_queue_structs;
-use crate::priority_queue_structs::Edge;
-use crate::priority_queue_structs::DisjointSet;
 use crate::priority_queue_structs::Branch;
 
 mod random_tsp;
 use crate::random_tsp::RandomTSPGenerator;
 
+mod guided_local_search;
+use crate::guided_local_search::get_guided_local_search;
+
+mod grasp;
+use crate::grasp::get_grasp;
+
+mod multi_start;
+use crate::multi_start::get_multi_start;
+
+mod held_karp;
+use crate::held_karp::{estimate_held_karp_memory, get_held_karp, is_held_karp_feasible};
+
+mod one_tree;
+use crate::one_tree::OneTreeBound;
+
+mod reduced_matrix;
+use crate::reduced_matrix::get_reduced_matrix_bnb;
+
+mod cutting_plane;
+use crate::cutting_plane::get_cutting_plane;
+
+mod search_limits;
+use crate::search_limits::{SearchLimits, SearchStats};
+
+mod bitset;
+use crate::bitset::Bitset;
+
+mod matching;
+
+mod christofides;
+use crate::christofides::get_christofides;
+
+mod greedy_edge;
+use crate::greedy_edge::get_greedy_edge;
+
+mod insertion;
+use crate::insertion::{get_convex_hull_insertion, get_insertion, InsertionRule};
+
+mod space_filling_curve;
+use crate::space_filling_curve::{get_space_filling_curve, SpaceFillingCurve};
+
+mod savings;
+use crate::savings::{get_central_node, get_savings};
+
+mod nearest_neighbor;
+use crate::nearest_neighbor::get_nearest_neighbor_tours;
+
+mod decomposition;
+use crate::decomposition::get_decomposition;
+
+mod candidate_graph;
+
+mod multilevel;
+use crate::multilevel::get_multilevel;
+
+mod tour_merging;
+use crate::tour_merging::get_merged_tour;
+
+mod window_exact;
+use crate::window_exact::get_windowed_exact;
+
+mod portfolio;
+use crate::portfolio::get_portfolio;
+
+mod open_path;
+use crate::open_path::{OpenPath, PathEnds};
+
+mod auto_select;
+use crate::auto_select::{configure_auto, get_instance_features};
+
+mod checkpoint;
+use crate::checkpoint::{load_checkpoint, Checkpoint, Checkpointer, Frontier};
+
 mod input_parsers;
 mod query;
 
+/* Subgradient iterations spent tightening the Held-Karp lower bound. */
+const BOUND_ITERATIONS: usize = 1000;
+
+/* Unvisited nodes above which branch and bound splits a node's children into tasks idle
+*  workers can steal. Smaller subtrees are cheaper to search in place. */
+const SPLIT_THRESHOLD: usize = 8;
+
+/* Margin a rearranged partial path has to win by before it counts as dominating, so rounding
+*  error never drops a path that ties the optimum. */
+const DOMINANCE_EPSILON: f64 = 1e-7;
+
+/* Partial routes branch and bound is split into when it saves checkpoints, and how many of
+*  them each thread searches between saves. */
+const CHECKPOINT_BRANCHES: usize = 1024;
+const CHECKPOINT_BATCH: usize = 4;
+
+/* Largest instance given a full distance map. Its n^2 / 2 entries take tens of GiB beyond this,
+*  so local search on larger instances goes through spatial decomposition instead. */
+const MAX_MAP_POINTS: usize = 20000;
+
+/* Local optima kept for tour merging, and the share of the run time merging may take on top. */
+const MERGE_POOL_SIZE: usize = 10;
+const MERGE_TIME_DIVISOR: u64 = 10;
+
 /* Round the number to avoid fp rounding errors. */
 fn round(number: f64) -> f64 {
     (number * 100000000.0).round() / 100000000.0
@@ -60,6 +157,43 @@ fn get_solution_length(map: &DistanceMap, solution: &Vec<u32>) -> (f64, bool){
     (length, is_complete)
 }
 
+/* Builds the starting tour for local search, and the starting bound for exact search. */
+fn get_construction(map: &DistanceMap, points: &Points, query: &query::UserQuery, seed: u64) -> Solution {
+    match query.construction {
+        query::Construction::NearestNeighbor => get_greedy(map),
+        query::Construction::Christofides => get_christofides(map),
+        query::Construction::GreedyEdge => get_greedy_edge(map),
+        query::Construction::NearestInsertion => get_insertion(map, InsertionRule::Nearest, seed),
+        query::Construction::FarthestInsertion => get_insertion(map, InsertionRule::Farthest, seed),
+        query::Construction::CheapestInsertion => get_insertion(map, InsertionRule::Cheapest, seed),
+        query::Construction::RandomInsertion => get_insertion(map, InsertionRule::Random, seed),
+        query::Construction::ConvexHullInsertion => get_convex_hull_insertion(map, points),
+        query::Construction::Hilbert => get_space_filling_curve(points, SpaceFillingCurve::Hilbert),
+        query::Construction::Sierpinski => get_space_filling_curve(points, SpaceFillingCurve::Sierpinski),
+        query::Construction::Savings => {
+            let depot = match query.depot {
+                Some(node) if (node as usize) < map.point_count() => node,
+                Some(node) => {
+                    println!("Depot {} is not a point of the instance. Using the most central point.", node);
+                    get_central_node(points)
+                }
+                None => get_central_node(points),
+            };
+            get_savings(map, depot)
+        }
+        query::Construction::MultiStartNearestNeighbor => get_nearest_neighbor_tours(map, query.starts, 1, seed).remove(0),
+    }
+}
+
+/* Tours 2-opt starts from. Multi-start nearest neighbor hands over its best few tours, the
+*  other constructions their one. */
+fn get_starting_pool(map: &DistanceMap, points: &Points, query: &query::UserQuery, seed: u64) -> Vec<Solution> {
+    match query.construction {
+        query::Construction::MultiStartNearestNeighbor => get_nearest_neighbor_tours(map, query.starts, query.pool_size, seed),
+        _ => vec![get_construction(map, points, query, seed)],
+    }
+}
+
 /* Finds the greedy solution to TSP. */
 fn get_greedy(map: &DistanceMap) -> Solution {
     let mut solution: Vec<u32> = vec![];
@@ -206,7 +340,7 @@ fn two_opt_swap(route: &Vec<u32>, v1: usize, v2: usize) -> Vec<u32> {
 }
 
 /* Determines the incremental gain of swapping edges */
-fn get_delta(map: &DistanceMap, solution: &Solution, i: &usize, j: &usize) -> f64 {
+fn get_delta<C: EdgeCost>(map: &C, solution: &Solution, i: &usize, j: &usize) -> f64 {
     let next_i = (i + 1) % solution.route.len();
     let next_j = (j + 1) % solution.route.len();
 
@@ -269,199 +403,664 @@ fn get_two_opt(map: &DistanceMap, solution_input: Solution) -> Solution {
     return_solution
 }
 
-/* Consider implementing and analyzing 3-opt. */
+/* Determines the incremental gain of moving the segment of `length` cities starting at `start`
+*  so that it sits between `target` and the city after it, optionally reversed. The segment may
+*  not wrap past the end of the route, and `target` must lie outside of it. */
+fn get_or_opt_delta<C: EdgeCost>(map: &C, route: &[u32], start: usize, length: usize, target: usize, reversed: bool) -> f64 {
+    let count = route.len();
+    let end = start + length - 1;
+
+    let before = route[(start + count - 1) % count];
+    let after = route[(end + 1) % count];
+    let first = route[start];
+    let last = route[end];
+    let target_node = route[target];
+    let target_next = route[(target + 1) % count];
+
+    let removed = map.get_distance_from_points(&before, &first)
+        + map.get_distance_from_points(&last, &after)
+        + map.get_distance_from_points(&target_node, &target_next);
+
+    let added = if reversed {
+        map.get_distance_from_points(&target_node, &last) + map.get_distance_from_points(&first, &target_next)
+    } else {
+        map.get_distance_from_points(&target_node, &first) + map.get_distance_from_points(&last, &target_next)
+    } + map.get_distance_from_points(&before, &after);
 
-/* Recursive branch and bound search function */
-fn branch_and_bound_recurse(solution: &mut Solution, 
-    map: &DistanceMap, 
-    bssf: &Arc<Mutex<f64>>, 
-    unvisited: & mut HashSet<u32>, 
-    best_solution: &Arc<Mutex<Solution>>,) {
+    round(added - removed)
+}
 
-    /* If it is a complete solution */
-    if &solution.len() == &map.len() {
-        let mut bssf_guard = bssf.lock().unwrap();
-        if solution.distance < *bssf_guard {
-            let additional_distance = map.get_distance_from_points(&solution.route.last().unwrap(), 
-                                                            &solution.route.first().unwrap());
-
-            if solution.distance + additional_distance < *bssf_guard {
-                *bssf_guard = solution.distance + additional_distance;
-                let mut best_solution_guard = best_solution.lock().unwrap();
-                *best_solution_guard = solution.clone();
-                best_solution_guard.distance = *bssf_guard;
-                drop(bssf_guard);
-                drop(best_solution_guard);
+/* Returns true if the segment starting at `start` can be moved after `target`. */
+fn is_or_opt_move_valid(count: usize, start: usize, length: usize, target: usize) -> bool {
+    if start + length > count || count < length + 3 {
+        return false;
+    }
+
+    // Inserting after the city before the segment, or inside of it, is not a move at all.
+    let before = (start + count - 1) % count;
+    target != before && (target < start || target >= start + length)
+}
+
+/* Move a segment of the route after `target`. */
+fn or_opt_move(route: &[u32], start: usize, length: usize, target: usize, reversed: bool) -> Vec<u32> {
+    let mut segment: Vec<u32> = route[start..start + length].to_vec();
+    if reversed {
+        segment.reverse();
+    }
+
+    let target_node = route[target];
+    let mut new_route = Vec::with_capacity(route.len());
+
+    for (index, node) in route.iter().enumerate() {
+        if index >= start && index < start + length {
+            continue;
+        }
+
+        new_route.push(*node);
+
+        if *node == target_node {
+            new_route.extend_from_slice(&segment);
+        }
+    }
+
+    new_route
+}
+
+/* Queue the given cities for another look by `local_descent`. */
+fn activate_cities(cities: &[u32], active: &mut VecDeque<u32>, queued: &mut [bool]) {
+    for city in cities {
+        if !queued[*city as usize] {
+            queued[*city as usize] = true;
+            active.push_back(*city);
+        }
+    }
+}
+
+/* Sequential 2-opt and Or-opt descent driven by don't-look bits. Only the queued cities are
+*  examined, and the endpoints of every improving move are queued again, so resuming after a
+*  small change to the route or to the costs is cheap. The solution distance is kept in the
+*  units of `map`, so callers searching on augmented costs must recompute the true length. */
+fn local_descent<C: EdgeCost>(map: &C, solution: &mut Solution, active: &mut VecDeque<u32>, queued: &mut [bool], deadline: Instant) {
+    let count = solution.len();
+
+    if count < 5 {
+        active.clear();
+        queued.fill(false);
+        return;
+    }
+
+    let mut position: Vec<usize> = vec![0; count];
+    for (index, node) in solution.route.iter().enumerate() {
+        position[*node as usize] = index;
+    }
+
+    while let Some(city) = active.pop_front() {
+        if Instant::now() >= deadline {
+            active.push_front(city);
+            return;
+        }
+
+        queued[city as usize] = false;
+        let index = position[city as usize];
+        let mut changed: Vec<u32> = Vec::new();
+
+        // 2-opt moves on both edges touching the city.
+        'two_opt: for edge in [(index + count - 1) % count, index] {
+            for other in 0..count {
+                if other == edge || (other + 1) % count == edge || (edge + 1) % count == other {
+                    continue;
+                }
+
+                let (i, j) = if edge < other { (edge, other) } else { (other, edge) };
+                let delta = get_delta(map, solution, &i, &j);
+
+                if delta < 0.0 {
+                    changed = vec![solution.route[i], solution.route[i + 1], solution.route[j], solution.route[(j + 1) % count]];
+                    solution.route = two_opt_swap(&solution.route, i, j);
+                    solution.distance += delta;
+                    break 'two_opt;
+                }
             }
         }
-    } else {
 
-        // Grab bssf and drop lock quickly to avoid clashing
-        let bssf_guard = bssf.lock().unwrap();
-        let stale_bssf = *bssf_guard;
-        drop(bssf_guard);
-
-        /* This could be a point for micro-optimization, but it's kind of a pain. */
-        let temp_nodes: Vec<u32> = unvisited.iter().cloned().collect();
-
-        for node in temp_nodes {
-            let additional_distance = map.get_distance_from_points(&node, solution.route.last().unwrap());
-
-            if solution.distance + additional_distance < stale_bssf {
-                solution.distance += additional_distance;
-                solution.route.push(node);
-                unvisited.remove(&node);
-                branch_and_bound_recurse(solution, map, bssf, unvisited, best_solution);
-                unvisited.insert(node);
-                solution.route.pop();
-                solution.distance -= additional_distance;
+        // Or-opt moves of the segments starting at the city.
+        if changed.is_empty() {
+            'or_opt: for length in 1..=3 {
+                for target in 0..count {
+                    if !is_or_opt_move_valid(count, index, length, target) {
+                        continue;
+                    }
+
+                    for reversed in [false, true] {
+                        let delta = get_or_opt_delta(map, &solution.route, index, length, target, reversed);
+
+                        if delta < 0.0 {
+                            changed = vec![solution.route[(index + count - 1) % count], solution.route[index],
+                                solution.route[index + length - 1], solution.route[(index + length) % count],
+                                solution.route[target], solution.route[(target + 1) % count]];
+                            solution.route = or_opt_move(&solution.route, index, length, target, reversed);
+                            solution.distance += delta;
+                            break 'or_opt;
+                        }
+                    }
+                }
+            }
+        }
+
+        if !changed.is_empty() {
+            changed.push(city);
+            activate_cities(&changed, active, queued);
+
+            for (index, node) in solution.route.iter().enumerate() {
+                position[*node as usize] = index;
             }
         }
     }
 }
 
-/* Parallelize the branch and bound search */
-fn parallel_branch_and_bound(map: &DistanceMap, bssf_input: f64, best_solution: &Solution) -> Solution {
-    /* Init mutex objects */
-    let bssf = Arc::new(Mutex::new(f64::from(bssf_input)));
-    let best_solution_arc = Arc::new(Mutex::new(best_solution.clone()));
+/* Runs `local_descent` over every city of the solution until it settles into a local minima,
+*  then recalculates the true route distance. */
+fn get_local_descent(map: &DistanceMap, solution_input: Solution, deadline: Instant) -> Solution {
+    let mut solution = solution_input;
+    let mut active: VecDeque<u32> = VecDeque::new();
+    let mut queued: Vec<bool> = vec![false; map.point_count()];
+    activate_cities(&solution.route, &mut active, &mut queued);
+
+    local_descent(map, &mut solution, &mut active, &mut queued, deadline);
+    solution.distance = get_solution_length(map, &solution.route).0;
+    solution
+}
 
-    // Assuming the start node is 0 and branching out to different nodes
-    let start_node = 0;
-    let unvisited: Vec<u32> = (1..map.len() as u32).collect();  // Starting from 1 as 0 is the start node
+/* Consider implementing and analyzing 3-opt. */
 
-    /* Parallel loop */
-    unvisited.par_iter().for_each(|&node| {
-        let bssf_clone = Arc::clone(&bssf);
-        let best_solution_clone = Arc::clone(&best_solution_arc);
-        let mut solution = Solution { route: vec![start_node, node], distance: map.get_distance_from_points(&start_node, &node) };
-        let mut unvisited_thread: HashSet<u32> = unvisited.iter().cloned().filter(|&n| n != node).collect();
+/* Every symmetric tour is searched in one direction only, the one where the city after the
+*  start is smaller than the city before returning to it. Returns true if the partial route can
+*  still end that way, given the cities it has left to visit in increasing order. */
+fn can_break_symmetry(route: &[u32], remaining: &[u32]) -> bool {
+    if route.len() < 2 {
+        return true;
+    }
 
-        /* Recursive call will return the optimal solution in the best_solution_arc memory location */
-        branch_and_bound_recurse(&mut solution, map, &bssf_clone, &mut unvisited_thread, &best_solution_clone);
-    });
+    let final_city = remaining.last().unwrap_or(route.last().unwrap());
+    *final_city > route[1]
+}
+
+/* Returns true if appending `node` to the route is beaten by rearranging the cities already on
+*  it while keeping both ends. Reversing the tail after route[i] is a 2-opt move, and moving the
+*  current last city between route[i] and route[i + 1] is an Or-opt (3-opt) move. Either one
+*  gives a strictly shorter path over the same cities to `node`, so no completion of this path
+*  can be optimal. */
+fn is_dominated(map: &DistanceMap, route: &[u32], node: u32) -> bool {
+    let length = route.len();
+    if length < 3 {
+        return false;
+    }
+
+    let last = route[length - 1];
+    let previous = route[length - 2];
+    let new_edge = map.get_distance_from_points(&last, &node);
+    let removal_gain = map.get_distance_from_points(&previous, &last) + new_edge - map.get_distance_from_points(&previous, &node);
+
+    for i in 0..length - 2 {
+        let (a, b) = (route[i], route[i + 1]);
+        let edge = map.get_distance_from_points(&a, &b);
+        let a_to_last = map.get_distance_from_points(&a, &last);
+
+        if a_to_last + map.get_distance_from_points(&b, &node) + DOMINANCE_EPSILON < edge + new_edge {
+            return true;
+        }
+
+        if a_to_last + map.get_distance_from_points(&last, &b) - edge + DOMINANCE_EPSILON < removal_gain {
+            return true;
+        }
+    }
 
-    let solution_clone = best_solution_arc.lock().unwrap().clone();
-    solution_clone
+    false
 }
 
-fn calculate_heuristic_estimate(map: &DistanceMap, branch: &Branch) -> f64 {
-    let unvisited: HashSet<u32> = (0..map.point_count() as u32)
-        .collect::<HashSet<_>>()
-        .difference(&branch.route.iter().cloned().collect())
-        .cloned()
-        .collect();
+/* Recursive branch and bound search function. The incumbent bound is read from an atomic, so
+*  the lock is only taken when a complete route actually beats it.
+*
+*  Only one direction of every tour is searched, partial paths a 2-opt or Or-opt move would
+*  shorten are dropped, and children are explored nearest first so good tours are found early
+*  and tighten bssf for the rest of the tree. */
+fn branch_and_bound_recurse(solution: &mut Solution, 
+    map: &DistanceMap, 
+    unvisited: &mut Bitset, 
+    incumbent: &Incumbent,
+    lower_bound: &OneTreeBound,
+    stats: &SearchStats,) {
+
+    if !stats.visit() {
+        return;
+    }
+
+    let first = solution.route[0];
+    let last = *solution.route.last().unwrap();
+    let remaining: Vec<u32> = unvisited.iter().collect();
 
-    // Example: Using the sum of the shortest distances from each unvisited node
-    unvisited.iter()
-        .map(|&node| {
-            unvisited.iter()
-                .filter(|&&other_node| node != other_node)
-                .map(|&other_node| map.get_distance_from_points(&node, &other_node))
-                .min_by(|a, b| a.partial_cmp(b).unwrap())
-                .unwrap_or(0.0)
+    if !can_break_symmetry(&solution.route, &remaining) {
+        stats.prune();
+        return;
+    }
+
+    /* If it is a complete solution */
+    if unvisited.is_empty() {
+        let distance = solution.distance + map.get_distance_from_points(&last, &first);
+        incumbent.offer(&Solution { route: solution.route.clone(), distance });
+        return;
+    }
+
+    let stale_bssf = incumbent.get_distance();
+
+    /* Prune the whole branch if even the 1-tree bound on finishing it can't beat bssf. */
+    let remaining_bound = lower_bound.get_remaining_bound(first, last, &remaining);
+    if solution.distance + remaining_bound >= stale_bssf {
+        stats.prune();
+        return;
+    }
+
+    let mut children: Vec<(u32, f64)> = remaining.into_iter()
+        .map(|node| (node, map.get_distance_from_points(&node, &last)))
+        .filter(|&(node, additional_distance)| {
+            let keep = solution.distance + additional_distance < stale_bssf && !is_dominated(map, &solution.route, node);
+            if !keep {
+                stats.prune();
+            }
+            keep
         })
-        .sum()
+        .collect();
+    children.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+    if unvisited.len() > SPLIT_THRESHOLD {
+        split_children(&children, solution, unvisited, map, incumbent, lower_bound, stats);
+        return;
+    }
+
+    for (node, additional_distance) in children {
+        // An earlier sibling may have lowered bssf since the children were filtered.
+        if solution.distance + additional_distance >= incumbent.get_distance() {
+            stats.prune();
+            continue;
+        }
+
+        solution.distance += additional_distance;
+        solution.route.push(node);
+        unvisited.remove(node);
+        branch_and_bound_recurse(solution, map, unvisited, incumbent, lower_bound, stats);
+        unvisited.insert(node);
+        solution.route.pop();
+        solution.distance -= additional_distance;
+    }
 }
 
-fn get_heuristic_kruskals(map: &DistanceMap) -> f64 {
-    // Collect edges
-    let mut edges = Vec::new();
-    for key in map.map.iter() {
-        edges.push(Edge {
-            node1: key.key().0,
-            node2: key.key().1,
-            distance: *key.value(),
-        });
+/* Search every child subtree as its own task. rayon::join only hands the second half to
+*  another thread if an idle worker steals it, so subtrees are donated on demand instead of
+*  being split up front, and a busy pool just runs them in order, nearest first. */
+fn split_children(children: &[(u32, f64)],
+    solution: &Solution,
+    unvisited: &Bitset,
+    map: &DistanceMap,
+    incumbent: &Incumbent,
+    lower_bound: &OneTreeBound,
+    stats: &SearchStats,) {
+
+    match children {
+        [] => {}
+        [(node, additional_distance)] => {
+            let mut child = Solution { route: solution.route.clone(), distance: solution.distance + additional_distance };
+            child.route.push(*node);
+
+            let mut child_unvisited = unvisited.clone();
+            child_unvisited.remove(*node);
+
+            branch_and_bound_recurse(&mut child, map, &mut child_unvisited, incumbent, lower_bound, stats);
+        }
+        _ => {
+            let (left, right) = children.split_at(children.len() / 2);
+            rayon::join(|| split_children(left, solution, unvisited, map, incumbent, lower_bound, stats),
+                || split_children(right, solution, unvisited, map, incumbent, lower_bound, stats));
+        }
     }
+}
 
-    // Sort edges by distance
-    edges.par_sort_unstable_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+/* Replace branches by their children a level at a time until there are at least `target` of
+*  them, then sort them so the shortest partial routes are searched first. */
+fn expand_frontier(map: &DistanceMap, frontier_input: Vec<Branch>, target: usize) -> Vec<Branch> {
+    let count = map.point_count();
+    let mut frontier = frontier_input;
+
+    while frontier.len() < target && frontier.iter().all(|branch| branch.route.len() + 2 < count) {
+        frontier = frontier.iter()
+            .flat_map(|branch| {
+                let last = *branch.route.last().unwrap();
+                let mut in_route = vec![false; count];
+                for node in branch.route.iter() {
+                    in_route[*node as usize] = true;
+                }
 
-    // Create MST using disjoint set
-    let mut ds = DisjointSet::new(map.point_count());
-    let mut mst_weight = 0.0;
-    for edge in edges {
-        let x = ds.find(edge.node1);
-        let y = ds.find(edge.node2);
+                (0..count as u32).filter(move |node| !in_route[*node as usize]).map(move |node| {
+                    let mut route = branch.route.clone();
+                    route.push(node);
+                    Branch { route, total_distance: branch.total_distance + map.get_distance_from_points(&last, &node), heuristic_estimate: 0.0 }
+                })
+            })
+            .collect();
+    }
 
-        if x != y {
-            mst_weight += edge.distance;
-            ds.union(x, y);
+    frontier.sort_by(|a, b| a.total_distance.partial_cmp(&b.total_distance).unwrap());
+    frontier
+}
+
+/* Parallelize the branch and bound search over a frontier of partial routes, node 0 alone for a
+*  fresh search. Every branch splits itself wherever workers are idle.
+*
+*  With a checkpointer the frontier is first expanded to CHECKPOINT_BRANCHES partial routes,
+*  which are searched a batch at a time so the ones left can be saved in between. A batch the
+*  search stops in is saved whole, since its branches are only partly explored. */
+fn parallel_branch_and_bound(map: &DistanceMap, bssf_input: f64, best_solution_input: &Solution, frontier_input: Vec<Branch>,
+    lower_bound: &OneTreeBound, stats: &SearchStats, checkpointer: Option<&Checkpointer>) -> Solution {
+    let mut bssf = bssf_input;
+    let mut best_solution = Solution { route: best_solution_input.route.clone(), distance: bssf };
+
+    // Up to three cities there is only one tour, in either direction.
+    if map.point_count() < 4 {
+        return best_solution;
+    }
+
+    let mut frontier = frontier_input;
+    let mut batch_size = frontier.len();
+
+    if checkpointer.is_some() {
+        frontier = expand_frontier(map, frontier, CHECKPOINT_BRANCHES);
+        batch_size = rayon::current_num_threads() * CHECKPOINT_BATCH;
+    }
+
+    while !frontier.is_empty() {
+        let rest = frontier.split_off(batch_size.min(frontier.len()));
+        let batch = std::mem::replace(&mut frontier, rest);
+
+        (bssf, best_solution) = depth_first_branches(map, batch.clone(), bssf, best_solution, lower_bound, stats);
+
+        if stats.is_stopped() {
+            frontier.splice(0..0, batch);
+        }
+
+        if let Some(checkpointer) = checkpointer.filter(|checkpointer| stats.is_stopped() || checkpointer.is_due()) {
+            checkpointer.save(&best_solution, stats, Frontier::Branches(frontier.clone()));
+        }
+
+        if stats.is_stopped() {
+            break;
         }
     }
 
-    mst_weight
+    best_solution
+}
+
+/* Estimated bytes held by one queued branch of the given route length. */
+fn get_branch_size(route_length: usize) -> u64 {
+    (std::mem::size_of::<Branch>() + route_length * std::mem::size_of::<u32>()) as u64
 }
 
-fn parallel_priority_queue_bnb(map: &DistanceMap, bssf_input: &mut f64,) -> Solution {
-    let mut best_solution = Solution::new();
-    let bssf = Arc::new(Mutex::new(f64::from(*bssf_input)));
+/* Save the best-first queue, along with any branches popped off it but not finished. */
+fn save_queue(checkpointer: &Checkpointer, best_solution: &Solution, stats: &SearchStats, queue: &BinaryHeap<Branch>, popped: &[Branch]) {
+    let mut branches: Vec<Branch> = popped.to_vec();
+    branches.extend(queue.iter().cloned());
 
-    let mut queue = BinaryHeap::new();
+    checkpointer.save(best_solution, stats, Frontier::Branches(branches));
+}
 
-    let initial_estimate: f64 = get_heuristic_kruskals(&map);
+/* Lowest priority among the branches still waiting, capped by bssf. Every tour the search
+*  hasn't ruled out lies under one of them, so this bounds the optimal tour when it stops. */
+fn get_frontier_bound(queue: &BinaryHeap<Branch>, popped: &[Branch], bssf: f64) -> f64 {
+    queue.iter().chain(popped.iter())
+        .map(|branch| branch.total_distance + branch.heuristic_estimate)
+        .fold(bssf, f64::min)
+}
+
+/* Run the depth-first search on each branch in parallel, sharing bssf between them. */
+fn depth_first_branches(map: &DistanceMap, branches: Vec<Branch>, bssf: f64, best_solution: Solution, lower_bound: &OneTreeBound, stats: &SearchStats) -> (f64, Solution) {
+    let incumbent = Incumbent::new(Solution { route: best_solution.route, distance: bssf });
+
+    branches.into_par_iter().for_each(|branch| {
+        let mut unvisited = Bitset::full(map.point_count());
+        for node in branch.route.iter() {
+            unvisited.remove(*node);
+        }
+
+        let mut solution = Solution { route: branch.route, distance: branch.total_distance };
+        branch_and_bound_recurse(&mut solution, map, &mut unvisited, &incumbent, lower_bound, stats);
+    });
+
+    let best_solution = incumbent.into_solution();
+    (best_solution.distance, best_solution)
+}
+
+/* Best-first branch and bound. Branches are expanded in order of route length plus the 1-tree
+*  bound on finishing them, so the first branch popped that can't beat bssf ends the search.
+*  Children are bounded in parallel against a snapshot of bssf.
+*
+*  Once the queue would outgrow `memory_limit` bytes, the best branches are handed to the
+*  depth-first search instead of being expanded, which keeps memory flat at the cost of
+*  search order.
+*
+*  Also returns a lower bound on the optimal tour. If the search is stopped early, that is the
+*  lowest priority still waiting in the queue or in the batch handed to the depth-first search.
+*
+*  The queue starts out as `frontier`, and a checkpointer saves it as the search goes. */
+fn parallel_priority_queue_bnb(map: &DistanceMap, best_solution_input: &Solution, memory_limit: u64,
+    frontier: Vec<Branch>, lower_bound: &OneTreeBound, stats: &SearchStats, checkpointer: Option<&Checkpointer>) -> (Solution, f64) {
+    let count = map.point_count();
+    let mut best_solution = best_solution_input.clone();
+    let mut bssf = best_solution.distance;
+
+    if count < 4 {
+        return (best_solution, bssf);
+    }
 
-    queue.push( Branch {route: vec![0], total_distance: 0.0, heuristic_estimate: initial_estimate, } );
+    let max_branches = (memory_limit / get_branch_size(count)).max(1) as usize;
+
+    let mut queue: BinaryHeap<Branch> = frontier.into_iter().collect();
 
     while let Some(branch) = queue.pop() {
-        if branch.route.len() == map.point_count() {
-            let mut bssf_guard = bssf.lock().unwrap();
-            let solution_distance = get_solution_length(map, &branch.route).0;
-            if solution_distance < *bssf_guard {
-                best_solution.route = branch.route.clone();
-                best_solution.distance = solution_distance;
-                *bssf_guard = solution_distance;
+        let priority = branch.total_distance + branch.heuristic_estimate;
+
+        if priority >= bssf {
+            break;
+        }
+
+        if let Some(checkpointer) = checkpointer.filter(|checkpointer| checkpointer.is_due()) {
+            save_queue(checkpointer, &best_solution, stats, &queue, std::slice::from_ref(&branch));
+        }
+
+        if !stats.visit() {
+            if let Some(checkpointer) = checkpointer {
+                save_queue(checkpointer, &best_solution, stats, &queue, std::slice::from_ref(&branch));
             }
-        } else {
-            // Generate new branches in parallel
-            let new_branches: Vec<Branch> = (0..map.point_count() as u32)
-                .into_par_iter()
-                .filter(|&node| !branch.route.contains(&node))
-                .map(|node| {
-                    let mut new_route = branch.route.clone();
-                    new_route.push(node);
-                    let new_total_distance = get_solution_length(&map, &new_route).0;
-                    let new_heuristic_estimate = calculate_heuristic_estimate(&map, &branch);
-                    Branch {
-                        route: new_route,
-                        total_distance: new_total_distance,
-                        heuristic_estimate: new_heuristic_estimate,
-                    }
-                })
-                .filter(|new_branch| new_branch.total_distance + new_branch.heuristic_estimate < *bssf.lock().unwrap())
-                .collect();
-    
-            // Sequentially insert new branches into the queue
-            for new_branch in new_branches {
-                queue.push(new_branch);
+            return (best_solution, get_frontier_bound(&queue, &[branch], bssf));
+        }
+
+        if queue.len() + count > max_branches {
+            let mut branches = vec![branch];
+            while branches.len() < rayon::current_num_threads() {
+                match queue.pop() {
+                    Some(next) if next.total_distance + next.heuristic_estimate < bssf => branches.push(next),
+                    _ => break,
+                }
             }
+
+            (bssf, best_solution) = depth_first_branches(map, branches.clone(), bssf, best_solution, lower_bound, stats);
+
+            if stats.is_stopped() {
+                if let Some(checkpointer) = checkpointer {
+                    save_queue(checkpointer, &best_solution, stats, &queue, &branches);
+                }
+                return (best_solution, get_frontier_bound(&queue, &branches, bssf));
+            }
+            continue;
+        }
+
+        let last = *branch.route.last().unwrap();
+        let unvisited: Vec<u32> = {
+            let mut in_route = vec![false; count];
+            for node in branch.route.iter() {
+                in_route[*node as usize] = true;
+            }
+            (0..count as u32).filter(|node| !in_route[*node as usize]).collect()
+        };
+
+        // Close the tour once a single node is left.
+        if unvisited.len() == 1 {
+            let node = unvisited[0];
+            let distance = branch.total_distance + map.get_distance_from_points(&last, &node)
+                + map.get_distance_from_points(&node, &0);
+
+            if distance < bssf {
+                bssf = distance;
+                best_solution.route = branch.route.clone();
+                best_solution.route.push(node);
+                best_solution.distance = distance;
+            } else {
+                stats.prune();
+            }
+            continue;
+        }
+
+        let stale_bssf = bssf;
+
+        // Generate and bound the new branches in parallel
+        let new_branches: Vec<Branch> = unvisited.par_iter()
+            .filter_map(|&node| {
+                let total_distance = branch.total_distance + map.get_distance_from_points(&last, &node);
+                let remaining: Vec<u32> = unvisited.iter().cloned().filter(|&other| other != node).collect();
+                let heuristic_estimate = lower_bound.get_remaining_bound(0, node, &remaining);
+
+                if total_distance + heuristic_estimate >= stale_bssf {
+                    stats.prune();
+                    return None;
+                }
+
+                let mut route = branch.route.clone();
+                route.push(node);
+
+                Some(Branch { route, total_distance, heuristic_estimate })
+            })
+            .collect();
+
+        // Sequentially insert new branches into the queue
+        for new_branch in new_branches {
+            queue.push(new_branch);
         }
     }
 
-    best_solution
+    (best_solution, bssf)
 }
 
 /* While this can return the optimal solution, the O(n!) worst-case time complexity makes
-   it a pretty bad idea to use if you are mapping more than ~16 points.
+   it a pretty bad idea to use branch and bound if you are mapping more than ~16 points.
 
    16 points runs for about 5 minutes. Not sure for 17 points.
+
+   Held-Karp runs in O(2^n n^2) instead, which is predictable up to ~25 points as long as its
+   table fits in memory, so it is preferred whenever it does.
    
    Additionally, the optimal solution actually typically returns the 2-opt local minima
-   solution. So the low chance of getting a better solution usually isn't worth the time.*/
-fn get_optimal(map: &DistanceMap, bssf: &mut f64, best_solution: &Solution) -> Solution {
-    let using_queue = false;
+   solution. So the low chance of getting a better solution usually isn't worth the time.
+   
+   A checkpointer saves the search as it goes, and a checkpoint picks up where an earlier
+   search left off. */
+fn get_optimal(map: &DistanceMap, best_solution: &Solution, method: &query::OptimalMethod, memory_limit: u64,
+    limits: SearchLimits, checkpointer: Option<&Checkpointer>, resume: Option<Checkpoint>) -> (Solution, SearchReport) {
+    let bssf = best_solution.distance;
+    let stats = match &resume {
+        Some(checkpoint) => SearchStats::with_counts(limits, checkpoint.nodes_explored, checkpoint.nodes_pruned),
+        None => SearchStats::new(limits),
+    };
+    let lower_bound = OneTreeBound::new(map, bssf, BOUND_ITERATIONS);
+
+    // Lower bound found by the solver itself, in case it has to stop early.
+    let mut solver_bound: f64 = 0.0;
+    let mut solution: Option<Solution> = None;
+
+    let (mut branches, completed_layers) = match resume.map(|checkpoint| checkpoint.frontier) {
+        Some(Frontier::Branches(branches)) => (Some(branches), 0),
+        Some(Frontier::HeldKarpLayers(layers)) => (None, layers),
+        Some(Frontier::Finished) => {
+            println!("The checkpoint holds a finished search.");
+            solution = Some(best_solution.clone());
+            (None, 0)
+        }
+        None => (None, 0),
+    };
 
-    if using_queue {
-        parallel_priority_queue_bnb(map, bssf)
-    } else {
+    // Held-Karp checkpoints hold branches once it has fallen back to branch and bound.
+    if solution.is_none() && *method == query::OptimalMethod::HeldKarp && branches.is_none() {
+        let estimate = estimate_held_karp_memory(map.point_count());
 
-        /* This one runs faster. 
-        *  Possibly try to improve this one with heuristic driling.*/
-        parallel_branch_and_bound(map, *bssf, best_solution)
+        if is_held_karp_feasible(map.point_count(), memory_limit) {
+            println!("Held-Karp needs about {:.1} MiB for {} points.", estimate as f64 / (1024.0 * 1024.0), map.point_count());
+            solution = Some(get_held_karp(map, &stats, checkpointer, best_solution, completed_layers)
+                .unwrap_or_else(|| best_solution.clone()));
+        } else {
+            println!("Held-Karp would need about {:.1} MiB for {} points. Falling back to branch and bound.",
+                estimate as f64 / (1024.0 * 1024.0), map.point_count());
+        }
     }
-    
-    /* Possibly replace branch and bound with the dynamic TSP algorithm in the future? */
+
+    if solution.is_none() && *method == query::OptimalMethod::BestFirst {
+        let frontier = branches.take()
+            .unwrap_or_else(|| vec![Branch { route: vec![0], total_distance: 0.0, heuristic_estimate: lower_bound.bound }]);
+        let (best_first_solution, best_first_bound) = parallel_priority_queue_bnb(map, best_solution, memory_limit, frontier,
+            &lower_bound, &stats, checkpointer);
+        solution = Some(best_first_solution);
+        solver_bound = best_first_bound;
+    }
+
+    if solution.is_none() && *method == query::OptimalMethod::ReducedMatrix {
+        let (reduced_solution, root_bound) = get_reduced_matrix_bnb(map, bssf, best_solution, &stats);
+        solution = Some(reduced_solution);
+        solver_bound = root_bound;
+    }
+
+    if solution.is_none() && *method == query::OptimalMethod::CuttingPlane {
+        let (cutting_solution, cutting_stats) = get_cutting_plane(map, best_solution, &stats);
+
+        println!("Branch and cut added {} subtour cuts and priced in {} edges.", cutting_stats.cuts, cutting_stats.priced_edges);
+        if cutting_stats.failed_nodes > 0 {
+            println!("The LP failed to converge on {} nodes.", cutting_stats.failed_nodes);
+        }
+
+        solution = Some(cutting_solution);
+        solver_bound = cutting_stats.root_bound;
+    }
+
+    /* This one runs faster. */
+    let solution = solution.unwrap_or_else(|| {
+        let frontier = branches.unwrap_or_else(|| vec![Branch { route: vec![0], total_distance: 0.0, heuristic_estimate: 0.0 }]);
+        parallel_branch_and_bound(map, bssf, best_solution, frontier, &lower_bound, &stats, checkpointer)
+    });
+
+    let proven_optimal = !stats.is_stopped();
+
+    if let Some(checkpointer) = checkpointer.filter(|_| proven_optimal) {
+        checkpointer.save(&solution, &stats, Frontier::Finished);
+    }
+    let bound = if proven_optimal {
+        solution.distance
+    } else {
+        lower_bound.bound.max(solver_bound).min(solution.distance)
+    };
+
+    let report = SearchReport {
+        lower_bound: bound,
+        gap: if bound > 0.0 { (solution.distance - bound) / bound } else { 0.0 },
+        nodes_explored: stats.get_nodes(),
+        nodes_pruned: stats.get_pruned(),
+        proven_optimal,
+    };
+
+    (solution, report)
 }
 
 fn parse_file(filename: &String) -> Points {
@@ -476,6 +1075,15 @@ fn parse_file(filename: &String) -> Points {
     points
 }
 
+fn parse_solution_file(filename: &str) -> Solution {
+    let mut file = File::open(filename).expect("File not found");
+
+    let mut data = String::new();
+    file.read_to_string(&mut data).expect("Failed to read file");
+
+    serde_json::from_str(&data).expect("Error while deserializing")
+}
+
 /* Return a random TSP solution for testing purposes. */
 fn get_random_solution(map: &DistanceMap) -> Solution {
     let mut vec: Vec<u32> = (0..map.len() as u32).collect();
@@ -509,51 +1117,283 @@ fn generate_points(query: query::UserQuery) {
 
 
 
-fn export_solution(solution: &Solution, filename: String) {
-    let json_string = serde_json::to_string_pretty(solution).expect("Error converting to JSON");
+fn export_reported_solution(solution: &Solution, report: Option<&SearchReport>, open_path: Option<&PathEnds>, filename: String) {
+    let reported_solution = ReportedSolution { solution, report, open_path };
+    let json_string = serde_json::to_string_pretty(&reported_solution).expect("Error converting to JSON");
     RandomTSPGenerator::write_to_file(json_string, filename);
 }
 
-fn solve_tsp(filename: &String, usage: &query::Usage, time: u64) -> Solution {
+/* Keep the MERGE_POOL_SIZE shortest distinct tours. Restarts that find the same local optima
+*  add nothing to the union, so tours of equal length are kept once. */
+fn add_to_merge_pool(pool: &mut Vec<Solution>, solution: Solution) {
+    if pool.iter().any(|tour| (tour.distance - solution.distance).abs() < 1e-9) {
+        return;
+    }
+
+    pool.push(solution);
+    pool.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+    pool.truncate(MERGE_POOL_SIZE);
+}
+
+/* Solve the query. Exact solves also return what the search proved about the solution, and
+*  continue from `resume` if it is given. */
+fn solve_tsp(query: &query::UserQuery, resume: Option<Checkpoint>) -> (Solution, Option<SearchReport>) {
     let start = Instant::now();
 
-    let map = get_map_from_file(filename);
+    let points: Points = parse_file(&query.filename);
 
-    let greedy_solution: Solution = get_greedy(&map);
+    if query.usage == query::Usage::SolveLocal
+        && (query.local_method == query::LocalMethod::Decomposition || points.points.len() > MAX_MAP_POINTS) {
+        if query.local_method != query::LocalMethod::Decomposition {
+            println!("{} points is too many for a distance map. Solving by spatial decomposition.", points.points.len());
+        }
+        if query.report_gap {
+            println!("The gap needs a distance map. Skipping it.");
+        }
+        if query.open_path {
+            println!("Open paths need a distance map, so spatial decomposition can't solve them.");
+            std::process::exit(1);
+        }
 
-    let mut best_solution: Solution = get_two_opt(&map, greedy_solution.clone());
+        let deadline = start + Duration::new(query.time as u64, 0);
+        return (get_decomposition(&points, query.cell_size, deadline), None);
+    }
+
+    let mut map = DistanceMap::new(&points);
+    let seed: u64 = query.seed.unwrap_or_else(|| thread_rng().gen());
+
+    for node in [query.path_start, query.path_end].into_iter().flatten() {
+        if node as usize >= map.point_count() {
+            println!("Path end {} is not a point of the instance.", node);
+            std::process::exit(1);
+        }
+    }
+    if query.path_start.is_some() && query.path_start == query.path_end {
+        println!("The path can't start and end at the same point. Solve it as a tour instead.");
+        std::process::exit(1);
+    }
+
+    let open_path = query.open_path.then(|| OpenPath::new(&mut map, &points, PathEnds { start: query.path_start, end: query.path_end }));
+    let map = map;
+
+    let mut best_solution: Solution = match &resume {
+        Some(checkpoint) => {
+            if checkpoint.point_count != map.point_count() {
+                println!("The checkpoint is for {} points, but {} holds {}.", checkpoint.point_count, query.filename, map.point_count());
+                std::process::exit(1);
+            }
+
+            checkpoint.incumbent.clone()
+        }
+        None => {
+            get_starting_pool(&map, &points, query, seed)
+                .into_par_iter()
+                .map(|solution| match &open_path {
+                    Some(open_path) => open_path.add_to_tour(&map, solution),
+                    None => solution,
+                })
+                .map(|solution| get_two_opt(&map, solution))
+                .min_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap())
+                .unwrap()
+        }
+    };
 
     let mut bssf: f64 = best_solution.distance;
+    let mut report: Option<SearchReport> = None;
+
+    if query.usage == query::Usage::SolveLocal {
+        let max_duration: Duration = Duration::new(query.time as u64, 0);
 
-    if *usage == query::Usage::SolveLocal {
-        let max_duration: Duration = Duration::new(time, 0);
+        let remaining = max_duration.saturating_sub(start.elapsed());
 
-        while start.elapsed() < max_duration {
-            let random_solution: Solution = get_random_solution(&map);
-            let new_solution: Solution = get_two_opt(&map, random_solution);
+        match query.local_method {
+            query::LocalMethod::GuidedLocalSearch => {
+                best_solution = get_guided_local_search(&map, best_solution, remaining);
+            }
+            query::LocalMethod::Grasp => {
+                let grasp_solution = get_grasp(&map, &best_solution, query.grasp_alpha, query.path_relinking, seed, remaining);
 
-            if round(new_solution.distance) != round(get_solution_length(&map, &new_solution.route).0) {
-                println!("get_two_opt is returning solutions with incorrect distances.")
+                if grasp_solution.distance < bssf {
+                    best_solution = grasp_solution;
+                }
             }
-            
-            if new_solution.distance < bssf {
-                bssf = new_solution.distance;
-                best_solution = new_solution.clone();
+            query::LocalMethod::MultiStart => {
+                best_solution = get_multi_start(&map, best_solution, query.islands, seed, remaining);
+            }
+            query::LocalMethod::Multilevel => {
+                let multilevel_solution = get_multilevel(&map, &points, query, &best_solution, seed, remaining);
+
+                if multilevel_solution.distance < bssf {
+                    best_solution = multilevel_solution;
+                }
+            }
+            query::LocalMethod::Portfolio => {
+                let (portfolio_solution, producer) = get_portfolio(&map, &points, best_solution.clone(), query, seed, remaining);
+
+                match producer {
+                    Some(method) => println!("The best tour was found by {:?}.", method),
+                    None => println!("No solver improved on the starting tour."),
+                }
+                best_solution = portfolio_solution;
+            }
+            // Solved before the distance map is built.
+            query::LocalMethod::Decomposition => unreachable!(),
+            query::LocalMethod::TwoOpt => {
+                let mut optima: Vec<Solution> = vec![best_solution.clone()];
+                let deadline = start + max_duration;
+
+                while start.elapsed() < max_duration {
+                    let random_solution: Solution = get_random_solution(&map);
+                    let new_solution: Solution = get_local_descent(&map, random_solution, deadline);
+
+                    if round(new_solution.distance) != round(get_solution_length(&map, &new_solution.route).0) {
+                        println!("get_local_descent is returning solutions with incorrect distances.")
+                    }
+
+                    if new_solution.distance < bssf {
+                        bssf = new_solution.distance;
+                        best_solution = new_solution.clone();
+                    }
+
+                    if query.merge_tours {
+                        add_to_merge_pool(&mut optima, new_solution);
+                    }
+                }
+
+                if query.merge_tours {
+                    let merge_time = Duration::new((query.time as u64 / MERGE_TIME_DIVISOR).max(1), 0);
+                    let stats = SearchStats::new(SearchLimits::new(Some(merge_time), None));
+                    let (merged, proven) = get_merged_tour(&map, &optima, &stats);
+
+                    println!("Merged {} tours, the best of length {}, into a tour of length {}{}.", optima.len(), optima[0].distance,
+                        merged.distance, if proven { ", the shortest over their edges" } else { "" });
+
+                    if merged.distance < bssf {
+                        best_solution = merged;
+                    }
+                }
             }
         }
+
+        if let Some(window_size) = query.window_size {
+            let windowed = get_windowed_exact(&map, best_solution.clone(), window_size);
+            println!("Exact windows of {} cities shortened the tour from {} to {}.", window_size, best_solution.distance, windowed.distance);
+            best_solution = windowed;
+        }
+    } else {
+        assert_eq!(query.usage, query::Usage::SolveOptimal);
+        let limits = SearchLimits::new(query.time_limit.map(|seconds| Duration::new(seconds, 0).saturating_sub(start.elapsed())),
+            query.node_limit);
+
+        let checkpointer = query.checkpoint_filename.as_ref().and_then(|filename| {
+            if matches!(query.optimal_method, query::OptimalMethod::ReducedMatrix | query::OptimalMethod::CuttingPlane) {
+                println!("This method can't save checkpoints. Running without them.");
+                return None;
+            }
+            if open_path.is_some() {
+                println!("Checkpoints don't record open paths. Running without them.");
+                return None;
+            }
+
+            // Resumed jobs may run from another directory.
+            let points_filename = std::fs::canonicalize(&query.filename)
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_else(|_| query.filename.clone());
+
+            Some(Checkpointer::new(filename, &points_filename, map.point_count(), query.optimal_method))
+        });
+
+        let (optimal_solution, optimal_report) = get_optimal(&map, &best_solution.clone(), &query.optimal_method,
+            query.memory_limit, limits, checkpointer.as_ref(), resume);
+        best_solution = optimal_solution;
+        report = Some(optimal_report);
+    }
+
+    if query.report_gap && report.is_none() {
+        print_lower_bound(&map, &best_solution, open_path.as_ref().map_or(0.0, |open_path| open_path.get_offset()));
+    }
+
+    if let Some(open_path) = &open_path {
+        best_solution = open_path.get_path(&map, &best_solution);
+        report = report.map(|report| open_path.get_path_report(&report, &best_solution));
+        println!("Cut the tour open into a path from {} to {} of length {}.", best_solution.route[0],
+            best_solution.route[best_solution.len() - 1], best_solution.distance);
+    }
+
+    (best_solution, report)
+}
+
+/* Print what an exact search proved about its solution. */
+fn print_search_report(report: &SearchReport) {
+    println!("Lower bound: {}", report.lower_bound);
+    println!("Gap: {:.4}%", report.gap * 100.0);
+    println!("Nodes explored: {}, pruned: {}", report.nodes_explored, report.nodes_pruned);
+
+    if report.proven_optimal {
+        println!("The solution is proven optimal.");
     } else {
-        assert_eq!(*usage, query::Usage::SolveOptimal);
-        best_solution = get_optimal(&map, &mut bssf, &best_solution.clone());
+        println!("The search stopped early, so the solution is not proven optimal.");
     }
+}
 
-    best_solution
+/* Print the Held-Karp lower bound and how far the solution could be from optimal. `offset` is
+*  taken off both, for tours standing in for open paths. */
+fn print_lower_bound(map: &DistanceMap, solution: &Solution, offset: f64) {
+    let mut lower_bound = OneTreeBound::new(map, solution.distance, BOUND_ITERATIONS);
+    lower_bound.bound -= offset;
+    let distance = solution.distance - offset;
+
+    println!("Lower bound: {}", lower_bound.bound);
+    println!("Distance: {}", distance);
+    println!("Gap: {:.4}%", lower_bound.get_gap(distance) * 100.0);
+}
+
+/* Report the lower bound for a points file. Without a solution file, the bound is measured
+*  against a quick greedy and local search tour. */
+fn report_bound(query: &query::UserQuery) {
+    let map = get_map_from_file(&query.filename);
+
+    let solution: Solution = match &query.solution_filename {
+        Some(solution_filename) => {
+            let mut solution = parse_solution_file(solution_filename);
+            let (distance, is_complete) = get_solution_length(&map, &solution.route);
+
+            if !is_complete {
+                println!("The solution does not visit every point.");
+                return;
+            }
+
+            solution.distance = distance;
+            solution
+        }
+        None => get_local_descent(&map, get_greedy(&map), Instant::now() + Duration::new(60, 0)),
+    };
+
+    print_lower_bound(&map, &solution, 0.0);
 }
 
 fn process_query(mut query: query::UserQuery) {
     if query.usage == query::Usage::Generate {
         generate_points(query);
+    } else if query.usage == query::Usage::Bound {
+        report_bound(&query);
     } else {
-        let best_solution = solve_tsp(&query.filename, &query.usage, query.time.clone() as u64);
+        let mut resume: Option<Checkpoint> = None;
+
+        if query.usage == query::Usage::Resume {
+            let checkpoint = load_checkpoint(query.checkpoint_filename.as_ref().unwrap());
+            query.filename = checkpoint.points_filename.clone();
+            query.optimal_method = checkpoint.method;
+            query.usage = query::Usage::SolveOptimal;
+            resume = Some(checkpoint);
+        }
+
+        if query.usage == query::Usage::Auto {
+            let features = get_instance_features(&parse_file(&query.filename));
+            configure_auto(&mut query, &features);
+        }
+
+        let (best_solution, report) = solve_tsp(&query, resume);
         
         let solution_type = if query.usage == query::Usage::SolveLocal { "_local" } else {"_optimal"};
 
@@ -565,7 +1405,12 @@ fn process_query(mut query: query::UserQuery) {
 
         query.filename.push_str("_solution.json");
 
-        export_solution(&best_solution, query.filename.clone());
+        if let Some(report) = &report {
+            print_search_report(report);
+        }
+
+        let path_ends = query.open_path.then_some(PathEnds { start: query.path_start, end: query.path_end });
+        export_reported_solution(&best_solution, report.as_ref(), path_ends.as_ref(), query.filename.clone());
     }
 }
 
@@ -582,7 +1427,12 @@ fn run_test() {
 
     println!("Finding local solution...");
 
-    let solution = solve_tsp(file_string, &query::Usage::SolveLocal, 0 as u64);
+    let mut query = query::UserQuery::new();
+    query.filename = file_string.clone();
+    query.usage = query::Usage::SolveLocal;
+    query.time = 0;
+
+    let (solution, _) = solve_tsp(&query, None);
 
     println!("Local solution found!");
     
@@ -622,9 +1472,308 @@ fn main() {
 
     if query.usage == query::Usage::Generate {
         input_parsers::parse_generate(&mut query, &args);
+    } else if query.usage == query::Usage::Resume {
+        input_parsers::parse_resume(&mut query, &args);
     } else {
         input_parsers::parse_solve(&mut query, &args);
     }
 
     process_query(query);
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point::Point;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /* Points scattered over a 1000 by 1000 square. Shared by the tests of every module. */
+    pub(crate) fn get_random_points(count: usize, seed: u64) -> Points {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        Points {
+            points: (0..count as u32)
+                .map(|id| Point { x: rng.gen_range(0.0..1000.0), y: rng.gen_range(0.0..1000.0), id })
+                .collect(),
+        }
+    }
+
+    /* Points in two clusters, the first few of them in pairs a hair apart. */
+    fn get_clustered_points(count: usize, seed: u64) -> Points {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut points: Vec<Point> = Vec::with_capacity(count);
+
+        for id in 0..count as u32 {
+            let point = match points.last() {
+                Some(last) if id < 4 && id % 2 == 1 => Point { x: last.x + rng.gen_range(0.0..0.01), y: last.y + rng.gen_range(0.0..0.01), id },
+                _ => {
+                    let center = if rng.gen_bool(0.5) { 250.0 } else { 750.0 };
+                    Point { x: center + rng.gen_range(-150.0..150.0), y: center + rng.gen_range(-150.0..150.0), id }
+                }
+            };
+            points.push(point);
+        }
+
+        Points { points }
+    }
+
+    fn get_held_karp_optimum(map: &DistanceMap) -> f64 {
+        let stats = SearchStats::new(SearchLimits::new(None, None));
+        get_held_karp(map, &stats, None, &Solution::new(), 0).unwrap().distance
+    }
+
+    fn solve_exactly(map: &DistanceMap, method: query::OptimalMethod, node_limit: Option<u64>) -> (Solution, SearchReport) {
+        let start = get_two_opt(map, get_greedy(map));
+        get_optimal(map, &start, &method, 4096 * 1024 * 1024, SearchLimits::new(None, node_limit), None, None)
+    }
+
+    fn assert_is_tour(map: &DistanceMap, solution: &Solution) {
+        let mut route = solution.route.clone();
+        route.sort_unstable();
+        assert_eq!(route, (0..map.point_count() as u32).collect::<Vec<u32>>());
+        assert!((get_solution_length(map, &solution.route).0 - solution.distance).abs() < 1e-6);
+    }
+
+    fn assert_matches_held_karp(method: query::OptimalMethod) {
+        for seed in 0..4 {
+            let map = DistanceMap::new(&get_random_points(10, seed));
+            let optimum = get_held_karp_optimum(&map);
+            let (solution, report) = solve_exactly(&map, method, None);
+
+            assert_is_tour(&map, &solution);
+            assert!((solution.distance - optimum).abs() < 1e-6, "{:?} found {} on seed {}, the optimum is {}",
+                method, solution.distance, seed, optimum);
+            assert!(report.proven_optimal);
+        }
+    }
+
+    /* Shortest tour by trying every order of the cities after the first. */
+    fn get_brute_force_optimum(map: &DistanceMap) -> f64 {
+        fn permute(map: &DistanceMap, route: &mut Vec<u32>, rest: &mut Vec<u32>, best: &mut f64) {
+            if rest.is_empty() {
+                *best = best.min(get_solution_length(map, route).0);
+                return;
+            }
+            for i in 0..rest.len() {
+                let node = rest.remove(i);
+                route.push(node);
+                permute(map, route, rest, best);
+                route.pop();
+                rest.insert(i, node);
+            }
+        }
+
+        let mut best = f64::INFINITY;
+        permute(map, &mut vec![0], &mut (1..map.point_count() as u32).collect(), &mut best);
+        best
+    }
+
+    #[test]
+    fn held_karp_matches_brute_force() {
+        for seed in 0..4 {
+            let map = DistanceMap::new(&get_random_points(8, seed));
+            let stats = SearchStats::new(SearchLimits::new(None, None));
+            let solution = get_held_karp(&map, &stats, None, &Solution::new(), 0).unwrap();
+
+            assert_is_tour(&map, &solution);
+            assert!((solution.distance - get_brute_force_optimum(&map)).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn best_first_matches_held_karp() {
+        assert_matches_held_karp(query::OptimalMethod::BestFirst);
+    }
+
+    #[test]
+    fn stopped_best_first_bound_stays_under_optimum() {
+        for seed in 100..116 {
+            let map = DistanceMap::new(&get_random_points(14, seed));
+            let optimum = get_held_karp_optimum(&map);
+
+            for nodes in [5, 20] {
+                let (solution, report) = solve_exactly(&map, query::OptimalMethod::BestFirst, Some(nodes));
+
+                assert_is_tour(&map, &solution);
+                assert!(report.lower_bound <= optimum + 1e-6, "bound {} over the optimum {} on seed {} after {} nodes",
+                    report.lower_bound, optimum, seed, nodes);
+                if report.gap <= 0.0 {
+                    assert!((solution.distance - optimum).abs() < 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reduced_matrix_matches_held_karp() {
+        assert_matches_held_karp(query::OptimalMethod::ReducedMatrix);
+    }
+
+    /* Clustered points leave the reduced bound weak, so the search may stop at its node limit.
+    *  Whatever it returns must still agree with Held-Karp. */
+    #[test]
+    fn reduced_matrix_matches_held_karp_on_clustered_points() {
+        for seed in 0..6 {
+            let map = DistanceMap::new(&get_clustered_points(16, seed));
+            let optimum = get_held_karp_optimum(&map);
+            let (solution, report) = solve_exactly(&map, query::OptimalMethod::ReducedMatrix, Some(200_000));
+
+            assert_is_tour(&map, &solution);
+            assert!(solution.distance >= optimum - 1e-6 && report.lower_bound <= optimum + 1e-6);
+            if report.proven_optimal {
+                assert!((solution.distance - optimum).abs() < 1e-6, "found {} on seed {}, the optimum is {}",
+                    solution.distance, seed, optimum);
+            }
+        }
+    }
+
+    #[test]
+    fn cutting_plane_matches_held_karp() {
+        assert_matches_held_karp(query::OptimalMethod::CuttingPlane);
+    }
+
+    /* Too many points for every edge to start out in the LP, so some have to be priced in. */
+    #[test]
+    fn cutting_plane_matches_branch_and_bound() {
+        let mut priced_edges = 0;
+
+        for seed in 0..3 {
+            let map = DistanceMap::new(&get_random_points(22, seed));
+            let (optimum, report) = solve_exactly(&map, query::OptimalMethod::BranchAndBound, None);
+            assert!(report.proven_optimal);
+
+            let stats = SearchStats::new(SearchLimits::new(None, None));
+            let (solution, cutting_stats) = get_cutting_plane(&map, &get_shuffled_tour(&map, seed), &stats);
+
+            assert_is_tour(&map, &solution);
+            assert!((solution.distance - optimum.distance).abs() < 1e-6, "found {} on seed {}, the optimum is {}",
+                solution.distance, seed, optimum.distance);
+            assert!(cutting_stats.root_bound <= optimum.distance + 1e-6);
+            priced_edges += cutting_stats.priced_edges;
+        }
+
+        assert!(priced_edges > 0);
+    }
+
+    #[test]
+    fn branch_and_bound_matches_held_karp() {
+        assert_matches_held_karp(query::OptimalMethod::BranchAndBound);
+    }
+
+    fn get_shuffled_tour(map: &DistanceMap, seed: u64) -> Solution {
+        let mut route: Vec<u32> = (0..map.point_count() as u32).collect();
+        route.shuffle(&mut StdRng::seed_from_u64(seed));
+        let distance = get_solution_length(map, &route).0;
+
+        Solution { route, distance }
+    }
+
+    #[test]
+    fn move_deltas_match_recomputed_lengths() {
+        let map = DistanceMap::new(&get_random_points(12, 0));
+        let solution = get_shuffled_tour(&map, 1);
+        let count = solution.len();
+
+        for i in 0..count {
+            for j in i + 2..count {
+                if (j + 1) % count == i {
+                    continue;
+                }
+                let swapped = get_solution_length(&map, &two_opt_swap(&solution.route, i, j)).0;
+                assert!((solution.distance + get_delta(&map, &solution, &i, &j) - swapped).abs() < 1e-6);
+            }
+        }
+
+        for start in 0..count {
+            for length in 1..=3 {
+                for target in 0..count {
+                    if !is_or_opt_move_valid(count, start, length, target) {
+                        continue;
+                    }
+                    for reversed in [false, true] {
+                        let moved = get_solution_length(&map, &or_opt_move(&solution.route, start, length, target, reversed)).0;
+                        let delta = get_or_opt_delta(&map, &solution.route, start, length, target, reversed);
+                        assert!((solution.distance + delta - moved).abs() < 1e-6);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn local_descent_keeps_its_distance_exact() {
+        for seed in 0..4 {
+            let map = DistanceMap::new(&get_random_points(60, seed));
+            let mut solution = get_shuffled_tour(&map, seed);
+            let start = solution.distance;
+
+            let mut active: VecDeque<u32> = VecDeque::new();
+            let mut queued: Vec<bool> = vec![false; map.point_count()];
+            activate_cities(&solution.route, &mut active, &mut queued);
+            local_descent(&map, &mut solution, &mut active, &mut queued, Instant::now() + Duration::new(60, 0));
+
+            assert_is_tour(&map, &solution);
+            assert!(solution.distance < start);
+        }
+    }
+
+    /* Shortest path through every city between the given ends, by trying every order. */
+    fn get_brute_force_path(map: &DistanceMap, ends: &PathEnds) -> f64 {
+        fn permute(map: &DistanceMap, ends: &PathEnds, route: &mut Vec<u32>, rest: &mut Vec<u32>, best: &mut f64) {
+            if rest.is_empty() {
+                if ends.end.is_none_or(|end| route.last() == Some(&end)) {
+                    let length: f64 = route.windows(2).map(|pair| map.get_distance_from_points(&pair[0], &pair[1])).sum();
+                    *best = best.min(length);
+                }
+                return;
+            }
+            for i in 0..rest.len() {
+                let node = rest.remove(i);
+                if route.is_empty() && ends.start.is_some_and(|start| start != node) {
+                    rest.insert(i, node);
+                    continue;
+                }
+                route.push(node);
+                permute(map, ends, route, rest, best);
+                route.pop();
+                rest.insert(i, node);
+            }
+        }
+
+        let mut best = f64::INFINITY;
+        permute(map, ends, &mut Vec::new(), &mut (0..map.point_count() as u32).collect(), &mut best);
+        best
+    }
+
+    #[test]
+    fn exact_methods_find_the_shortest_open_path() {
+        let methods = [query::OptimalMethod::HeldKarp, query::OptimalMethod::BranchAndBound, query::OptimalMethod::BestFirst,
+            query::OptimalMethod::ReducedMatrix, query::OptimalMethod::CuttingPlane];
+
+        for seed in 0..2 {
+            let points = get_random_points(8, seed);
+
+            for (start, end) in [(None, None), (Some(2), None), (None, Some(5)), (Some(2), Some(5))] {
+                let closed_map = DistanceMap::new(&points);
+                let optimum = get_brute_force_path(&closed_map, &PathEnds { start, end });
+
+                for method in methods {
+                    let mut map = DistanceMap::new(&points);
+                    let open_path = OpenPath::new(&mut map, &points, PathEnds { start, end });
+                    let (tour, report) = solve_exactly(&map, method, None);
+                    let path = open_path.get_path(&map, &tour);
+                    let report = open_path.get_path_report(&report, &path);
+
+                    let mut route = path.route.clone();
+                    route.sort_unstable();
+                    assert_eq!(route, (0..8).collect::<Vec<u32>>());
+                    assert!(start.is_none_or(|start| path.route[0] == start));
+                    assert!(end.is_none_or(|end| path.route[7] == end));
+                    assert!((path.distance - optimum).abs() < 1e-6, "{:?} found a path of {} from {:?} to {:?}, the optimum is {}",
+                        method, path.distance, start, end, optimum);
+                    assert!(report.proven_optimal && report.gap.abs() < 1e-9);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,639 @@
+/* Edmonds' blossom algorithm for maximum weight matching, in the O(n^3) primal-dual form
+*  described by Galil, "Efficient Algorithms for Finding Maximum Matching in Graphs" (1986).
+*
+*  Vertices are 0..n. Edge k joins edges[k].0 and edges[k].1, and its two endpoints are
+*  numbered 2k and 2k + 1, so `p ^ 1` is the other end of the edge endpoint p belongs to.
+*  Indices 0..n of the blossom arrays are the vertices themselves, n..2n are non-trivial
+*  blossoms. -1 stands for none. Weights are integers, so every dual stays exact. */
+struct BlossomMatching {
+    vertex_count: usize,
+    edges: Vec<(usize, usize, i64)>,
+    endpoint: Vec<usize>,
+    neighbour_ends: Vec<Vec<usize>>,
+    mate: Vec<isize>,
+    /* 0 is free, 1 is an S (outer) label, 2 is a T (inner) label. */
+    label: Vec<i8>,
+    label_end: Vec<isize>,
+    in_blossom: Vec<usize>,
+    blossom_parent: Vec<isize>,
+    blossom_children: Vec<Vec<usize>>,
+    blossom_base: Vec<isize>,
+    blossom_ends: Vec<Vec<usize>>,
+    best_edge: Vec<isize>,
+    blossom_best_edges: Vec<Option<Vec<usize>>>,
+    unused_blossoms: Vec<usize>,
+    dual: Vec<i64>,
+    allowed: Vec<bool>,
+    queue: Vec<usize>,
+}
+
+impl BlossomMatching {
+    fn new(vertex_count: usize, edges: Vec<(usize, usize, i64)>) -> BlossomMatching {
+        let max_weight = edges.iter().map(|edge| edge.2).max().unwrap_or(0).max(0);
+        let endpoint: Vec<usize> = (0..2 * edges.len()).map(|p| if p % 2 == 0 { edges[p / 2].0 } else { edges[p / 2].1 }).collect();
+
+        let mut neighbour_ends: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+        for (k, &(i, j, _)) in edges.iter().enumerate() {
+            neighbour_ends[i].push(2 * k + 1);
+            neighbour_ends[j].push(2 * k);
+        }
+
+        let edge_count = edges.len();
+
+        BlossomMatching {
+            vertex_count,
+            edges,
+            endpoint,
+            neighbour_ends,
+            mate: vec![-1; vertex_count],
+            label: vec![0; 2 * vertex_count],
+            label_end: vec![-1; 2 * vertex_count],
+            in_blossom: (0..vertex_count).collect(),
+            blossom_parent: vec![-1; 2 * vertex_count],
+            blossom_children: vec![Vec::new(); 2 * vertex_count],
+            blossom_base: (0..vertex_count as isize).chain(std::iter::repeat_n(-1, vertex_count)).collect(),
+            blossom_ends: vec![Vec::new(); 2 * vertex_count],
+            best_edge: vec![-1; 2 * vertex_count],
+            blossom_best_edges: vec![None; 2 * vertex_count],
+            unused_blossoms: (vertex_count..2 * vertex_count).collect(),
+            dual: std::iter::repeat_n(max_weight, vertex_count).chain(std::iter::repeat_n(0, vertex_count)).collect(),
+            allowed: vec![false; edge_count],
+            queue: Vec::new(),
+        }
+    }
+
+    fn slack(&self, k: usize) -> i64 {
+        let (i, j, weight) = self.edges[k];
+        self.dual[i] + self.dual[j] - 2 * weight
+    }
+
+    /* Every vertex inside blossom b, however deeply nested. */
+    fn get_leaves(&self, b: usize) -> Vec<usize> {
+        if b < self.vertex_count {
+            return vec![b];
+        }
+
+        self.blossom_children[b].iter().flat_map(|&child| self.get_leaves(child)).collect()
+    }
+
+    /* Child of blossom b at a position that may run negative, counting from the end. */
+    fn get_child(&self, b: usize, j: isize) -> usize {
+        let children = &self.blossom_children[b];
+        children[j.rem_euclid(children.len() as isize) as usize]
+    }
+
+    fn get_end(&self, b: usize, j: isize) -> usize {
+        let ends = &self.blossom_ends[b];
+        ends[j.rem_euclid(ends.len() as isize) as usize]
+    }
+
+    /* Label vertex w and its top level blossom with t, reached through endpoint p. A T label
+    *  also labels the mate of the blossom's base S. */
+    fn assign_label(&mut self, w: usize, t: i8, p: isize) {
+        let b = self.in_blossom[w];
+        self.label[w] = t;
+        self.label[b] = t;
+        self.label_end[w] = p;
+        self.label_end[b] = p;
+        self.best_edge[w] = -1;
+        self.best_edge[b] = -1;
+
+        if t == 1 {
+            let leaves = self.get_leaves(b);
+            self.queue.extend(leaves);
+        } else if t == 2 {
+            let base = self.blossom_base[b] as usize;
+            let mate = self.mate[base];
+            self.assign_label(self.endpoint[mate as usize], 1, mate ^ 1);
+        }
+    }
+
+    /* Trace back from v and w towards the roots of their trees. Returns the base of the new
+    *  blossom if the paths meet, or -1 if they reach two different roots. */
+    fn scan_blossom(&mut self, v_input: usize, w_input: usize) -> isize {
+        let mut path: Vec<usize> = Vec::new();
+        let mut base: isize = -1;
+        let mut v = v_input as isize;
+        let mut w = w_input as isize;
+
+        while v != -1 || w != -1 {
+            let mut b = self.in_blossom[v as usize];
+
+            if self.label[b] & 4 != 0 {
+                base = self.blossom_base[b];
+                break;
+            }
+
+            path.push(b);
+            self.label[b] = 5;
+
+            if self.label_end[b] == -1 {
+                v = -1;
+            } else {
+                v = self.endpoint[self.label_end[b] as usize] as isize;
+                b = self.in_blossom[v as usize];
+                v = self.endpoint[self.label_end[b] as usize] as isize;
+            }
+
+            if w != -1 {
+                std::mem::swap(&mut v, &mut w);
+            }
+        }
+
+        for b in path {
+            self.label[b] = 1;
+        }
+
+        base
+    }
+
+    /* Shrink the odd cycle closed by edge k, with the given base, into a new S blossom. */
+    fn add_blossom(&mut self, base: usize, k: usize) {
+        let (mut v, mut w, _) = self.edges[k];
+        let base_blossom = self.in_blossom[base];
+        let mut bv = self.in_blossom[v];
+        let mut bw = self.in_blossom[w];
+
+        let b = self.unused_blossoms.pop().unwrap();
+        self.blossom_base[b] = base as isize;
+        self.blossom_parent[b] = -1;
+        self.blossom_parent[base_blossom] = b as isize;
+
+        let mut path: Vec<usize> = Vec::new();
+        let mut ends: Vec<usize> = Vec::new();
+
+        while bv != base_blossom {
+            self.blossom_parent[bv] = b as isize;
+            path.push(bv);
+            ends.push(self.label_end[bv] as usize);
+            v = self.endpoint[self.label_end[bv] as usize];
+            bv = self.in_blossom[v];
+        }
+
+        path.push(base_blossom);
+        path.reverse();
+        ends.reverse();
+        ends.push(2 * k);
+
+        while bw != base_blossom {
+            self.blossom_parent[bw] = b as isize;
+            path.push(bw);
+            ends.push((self.label_end[bw] ^ 1) as usize);
+            w = self.endpoint[self.label_end[bw] as usize];
+            bw = self.in_blossom[w];
+        }
+
+        self.label[b] = 1;
+        self.label_end[b] = self.label_end[base_blossom];
+        self.dual[b] = 0;
+        self.blossom_children[b] = path.clone();
+        self.blossom_ends[b] = ends;
+
+        for leaf in self.get_leaves(b) {
+            if self.label[self.in_blossom[leaf]] == 2 {
+                self.queue.push(leaf);
+            }
+            self.in_blossom[leaf] = b;
+        }
+
+        // Least slack edge from the new blossom to every other S blossom.
+        let mut best_edge_to: Vec<isize> = vec![-1; 2 * self.vertex_count];
+
+        for &child in path.iter() {
+            let edge_lists: Vec<Vec<usize>> = match self.blossom_best_edges[child].take() {
+                Some(list) => vec![list],
+                None => self.get_leaves(child).iter()
+                    .map(|&leaf| self.neighbour_ends[leaf].iter().map(|p| p / 2).collect())
+                    .collect(),
+            };
+
+            for edge_list in edge_lists {
+                for k in edge_list {
+                    let (i, j, _) = self.edges[k];
+                    let j = if self.in_blossom[j] == b { i } else { j };
+                    let bj = self.in_blossom[j];
+
+                    if bj != b && self.label[bj] == 1
+                        && (best_edge_to[bj] == -1 || self.slack(k) < self.slack(best_edge_to[bj] as usize)) {
+                        best_edge_to[bj] = k as isize;
+                    }
+                }
+            }
+
+            self.best_edge[child] = -1;
+        }
+
+        let best_edges: Vec<usize> = best_edge_to.into_iter().filter(|&k| k != -1).map(|k| k as usize).collect();
+
+        self.best_edge[b] = -1;
+        for &k in best_edges.iter() {
+            if self.best_edge[b] == -1 || self.slack(k) < self.slack(self.best_edge[b] as usize) {
+                self.best_edge[b] = k as isize;
+            }
+        }
+        self.blossom_best_edges[b] = Some(best_edges);
+    }
+
+    /* Undo blossom b. Mid stage, a T blossom's children are relabelled so the alternating
+    *  path through it stays intact. At the end of a stage, zero dual sub-blossoms go too. */
+    fn expand_blossom(&mut self, b: usize, end_stage: bool) {
+        for child in self.blossom_children[b].clone() {
+            self.blossom_parent[child] = -1;
+
+            if child < self.vertex_count {
+                self.in_blossom[child] = child;
+            } else if end_stage && self.dual[child] == 0 {
+                self.expand_blossom(child, end_stage);
+            } else {
+                for leaf in self.get_leaves(child) {
+                    self.in_blossom[leaf] = child;
+                }
+            }
+        }
+
+        if !end_stage && self.label[b] == 2 {
+            let entry_child = self.in_blossom[self.endpoint[(self.label_end[b] ^ 1) as usize]];
+            let length = self.blossom_children[b].len() as isize;
+            let mut j = self.blossom_children[b].iter().position(|&child| child == entry_child).unwrap() as isize;
+
+            // Walk the even length way around the cycle from the entry child to the base.
+            let (step, end_trick): (isize, isize) = if j & 1 != 0 {
+                j -= length;
+                (1, 0)
+            } else {
+                (-1, 1)
+            };
+
+            let mut p = self.label_end[b];
+            while j != 0 {
+                self.label[self.endpoint[(p ^ 1) as usize]] = 0;
+                let end = self.get_end(b, j - end_trick) as isize;
+                self.label[self.endpoint[(end ^ end_trick ^ 1) as usize]] = 0;
+                self.assign_label(self.endpoint[(p ^ 1) as usize], 2, p);
+                self.allowed[end as usize / 2] = true;
+
+                j += step;
+                p = self.get_end(b, j - end_trick) as isize ^ end_trick;
+                self.allowed[p as usize / 2] = true;
+                j += step;
+            }
+
+            let bv = self.get_child(b, j);
+            let entry = self.endpoint[(p ^ 1) as usize];
+            self.label[entry] = 2;
+            self.label[bv] = 2;
+            self.label_end[entry] = p;
+            self.label_end[bv] = p;
+            self.best_edge[bv] = -1;
+            j += step;
+
+            while self.get_child(b, j) != entry_child {
+                let bv = self.get_child(b, j);
+
+                if self.label[bv] == 1 {
+                    j += step;
+                    continue;
+                }
+
+                let labelled = self.get_leaves(bv).into_iter().find(|&leaf| self.label[leaf] != 0);
+                if let Some(leaf) = labelled {
+                    self.label[leaf] = 0;
+                    let mate = self.mate[self.blossom_base[bv] as usize];
+                    self.label[self.endpoint[mate as usize]] = 0;
+                    self.assign_label(leaf, 2, self.label_end[leaf]);
+                }
+
+                j += step;
+            }
+        }
+
+        self.label[b] = -1;
+        self.label_end[b] = -1;
+        self.blossom_children[b] = Vec::new();
+        self.blossom_ends[b] = Vec::new();
+        self.blossom_base[b] = -1;
+        self.blossom_best_edges[b] = None;
+        self.best_edge[b] = -1;
+        self.unused_blossoms.push(b);
+    }
+
+    /* Swap matched and unmatched edges along the path inside blossom b from vertex v to the
+    *  base, which makes v the new base. */
+    fn augment_blossom(&mut self, b: usize, v: usize) {
+        let mut t = v;
+        while self.blossom_parent[t] != b as isize {
+            t = self.blossom_parent[t] as usize;
+        }
+
+        if t >= self.vertex_count {
+            self.augment_blossom(t, v);
+        }
+
+        let length = self.blossom_children[b].len() as isize;
+        let i = self.blossom_children[b].iter().position(|&child| child == t).unwrap();
+        let mut j = i as isize;
+
+        let (step, end_trick): (isize, isize) = if j & 1 != 0 {
+            j -= length;
+            (1, 0)
+        } else {
+            (-1, 1)
+        };
+
+        while j != 0 {
+            j += step;
+            let child = self.get_child(b, j);
+            let p = self.get_end(b, j - end_trick) ^ end_trick as usize;
+            if child >= self.vertex_count {
+                self.augment_blossom(child, self.endpoint[p]);
+            }
+
+            j += step;
+            let child = self.get_child(b, j);
+            if child >= self.vertex_count {
+                self.augment_blossom(child, self.endpoint[p ^ 1]);
+            }
+
+            self.mate[self.endpoint[p]] = (p ^ 1) as isize;
+            self.mate[self.endpoint[p ^ 1]] = p as isize;
+        }
+
+        self.blossom_children[b].rotate_left(i);
+        self.blossom_ends[b].rotate_left(i);
+        self.blossom_base[b] = self.blossom_base[self.blossom_children[b][0]];
+    }
+
+    /* Flip the augmenting path through edge k, which joins two S vertices of different trees. */
+    fn augment_matching(&mut self, k: usize) {
+        let (v, w, _) = self.edges[k];
+
+        for (start, start_end) in [(v, 2 * k + 1), (w, 2 * k)] {
+            let mut s = start;
+            let mut p = start_end as isize;
+
+            loop {
+                let bs = self.in_blossom[s];
+                if bs >= self.vertex_count {
+                    self.augment_blossom(bs, s);
+                }
+
+                self.mate[s] = p;
+
+                if self.label_end[bs] == -1 {
+                    break;
+                }
+
+                let t = self.endpoint[self.label_end[bs] as usize];
+                let bt = self.in_blossom[t];
+                s = self.endpoint[self.label_end[bt] as usize];
+                let j = self.endpoint[(self.label_end[bt] ^ 1) as usize];
+
+                if bt >= self.vertex_count {
+                    self.augment_blossom(bt, j);
+                }
+
+                self.mate[j] = self.label_end[bt];
+                p = self.label_end[bt] ^ 1;
+            }
+        }
+    }
+
+    /* Runs one stage per augmentation until none is left. Maximum cardinality comes first,
+    *  then maximum weight among those. Returns every vertex's mate. */
+    fn solve(mut self) -> Vec<isize> {
+        let n = self.vertex_count;
+
+        for _ in 0..n {
+            self.label.iter_mut().for_each(|label| *label = 0);
+            self.best_edge.iter_mut().for_each(|edge| *edge = -1);
+            self.blossom_best_edges[n..].iter_mut().for_each(|edges| *edges = None);
+            self.allowed.iter_mut().for_each(|allowed| *allowed = false);
+            self.queue.clear();
+
+            for v in 0..n {
+                if self.mate[v] == -1 && self.label[self.in_blossom[v]] == 0 {
+                    self.assign_label(v, 1, -1);
+                }
+            }
+
+            let mut augmented = false;
+
+            loop {
+                while let Some(v) = (!augmented).then(|| self.queue.pop()).flatten() {
+                    for p in self.neighbour_ends[v].clone() {
+                        let k = p / 2;
+                        let w = self.endpoint[p];
+
+                        if self.in_blossom[v] == self.in_blossom[w] {
+                            continue;
+                        }
+
+                        let mut slack = 0;
+                        if !self.allowed[k] {
+                            slack = self.slack(k);
+                            if slack <= 0 {
+                                self.allowed[k] = true;
+                            }
+                        }
+
+                        if self.allowed[k] {
+                            if self.label[self.in_blossom[w]] == 0 {
+                                self.assign_label(w, 2, (p ^ 1) as isize);
+                            } else if self.label[self.in_blossom[w]] == 1 {
+                                let base = self.scan_blossom(v, w);
+                                if base >= 0 {
+                                    self.add_blossom(base as usize, k);
+                                } else {
+                                    self.augment_matching(k);
+                                    augmented = true;
+                                    break;
+                                }
+                            } else if self.label[w] == 0 {
+                                self.label[w] = 2;
+                                self.label_end[w] = (p ^ 1) as isize;
+                            }
+                        } else if self.label[self.in_blossom[w]] == 1 {
+                            let b = self.in_blossom[v];
+                            if self.best_edge[b] == -1 || slack < self.slack(self.best_edge[b] as usize) {
+                                self.best_edge[b] = k as isize;
+                            }
+                        } else if self.label[w] == 0
+                            && (self.best_edge[w] == -1 || slack < self.slack(self.best_edge[w] as usize)) {
+                            self.best_edge[w] = k as isize;
+                        }
+                    }
+                }
+
+                if augmented {
+                    break;
+                }
+
+                // No augmenting path with the current duals, so find the largest safe dual change.
+                let mut delta_type = -1;
+                let mut delta: i64 = 0;
+                let mut delta_edge: usize = 0;
+                let mut delta_blossom: usize = 0;
+
+                for v in 0..n {
+                    if self.label[self.in_blossom[v]] == 0 && self.best_edge[v] != -1 {
+                        let d = self.slack(self.best_edge[v] as usize);
+                        if delta_type == -1 || d < delta {
+                            delta = d;
+                            delta_type = 2;
+                            delta_edge = self.best_edge[v] as usize;
+                        }
+                    }
+                }
+
+                for b in 0..2 * n {
+                    if self.blossom_parent[b] == -1 && self.label[b] == 1 && self.best_edge[b] != -1 {
+                        let d = self.slack(self.best_edge[b] as usize) / 2;
+                        if delta_type == -1 || d < delta {
+                            delta = d;
+                            delta_type = 3;
+                            delta_edge = self.best_edge[b] as usize;
+                        }
+                    }
+                }
+
+                for b in n..2 * n {
+                    if self.blossom_base[b] >= 0 && self.blossom_parent[b] == -1 && self.label[b] == 2
+                        && (delta_type == -1 || self.dual[b] < delta) {
+                        delta = self.dual[b];
+                        delta_type = 4;
+                        delta_blossom = b;
+                    }
+                }
+
+                if delta_type == -1 {
+                    // Nothing left to grow, so the matching has maximum cardinality.
+                    delta_type = 1;
+                    delta = self.dual[..n].iter().cloned().min().unwrap_or(0).max(0);
+                }
+
+                for v in 0..n {
+                    match self.label[self.in_blossom[v]] {
+                        1 => self.dual[v] -= delta,
+                        2 => self.dual[v] += delta,
+                        _ => {}
+                    }
+                }
+
+                for b in n..2 * n {
+                    if self.blossom_base[b] >= 0 && self.blossom_parent[b] == -1 {
+                        match self.label[b] {
+                            1 => self.dual[b] += delta,
+                            2 => self.dual[b] -= delta,
+                            _ => {}
+                        }
+                    }
+                }
+
+                match delta_type {
+                    1 => break,
+                    2 => {
+                        self.allowed[delta_edge] = true;
+                        let (i, j, _) = self.edges[delta_edge];
+                        let i = if self.label[self.in_blossom[i]] == 0 { j } else { i };
+                        self.queue.push(i);
+                    }
+                    3 => {
+                        self.allowed[delta_edge] = true;
+                        let (i, _, _) = self.edges[delta_edge];
+                        self.queue.push(i);
+                    }
+                    _ => self.expand_blossom(delta_blossom, false),
+                }
+            }
+
+            if !augmented {
+                break;
+            }
+
+            // Expand S blossoms whose dual dropped to zero.
+            for b in n..2 * n {
+                if self.blossom_parent[b] == -1 && self.blossom_base[b] >= 0 && self.label[b] == 1 && self.dual[b] == 0 {
+                    self.expand_blossom(b, true);
+                }
+            }
+        }
+
+        (0..n).map(|v| if self.mate[v] >= 0 { self.endpoint[self.mate[v] as usize] as isize } else { -1 }).collect()
+    }
+}
+
+/* Minimum weight perfect matching on the complete graph over 0..count, for an even count.
+*  Costs are scaled to integers, so the result is exact up to about one part in 10^9 of the
+*  largest cost. Returns the mate of every vertex. */
+pub fn get_min_weight_perfect_matching<F>(count: usize, cost: F) -> Vec<usize>
+where F: Fn(usize, usize) -> f64 {
+    let costs: Vec<(usize, usize, f64)> = (0..count)
+        .flat_map(|i| (i + 1..count).map(move |j| (i, j)))
+        .map(|(i, j)| (i, j, cost(i, j)))
+        .collect();
+
+    let max_cost = costs.iter().map(|edge| edge.2).fold(0.0, f64::max);
+    let scale = if max_cost > 0.0 { 1e9 / max_cost } else { 1.0 };
+
+    // Maximum cardinality forces a perfect matching, and flipping the weights makes the
+    // maximum weight one the cheapest.
+    let edges: Vec<(usize, usize, i64)> = costs.into_iter()
+        .map(|(i, j, cost)| (i, j, 1_000_000_001 - (cost * scale).round() as i64))
+        .collect();
+
+    BlossomMatching::new(count, edges).solve()
+        .into_iter()
+        .map(|mate| mate as usize)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /* Cheapest perfect matching of the unmatched vertices, pairing off the lowest one first. */
+    fn get_brute_force_cost(costs: &[Vec<f64>], matched: &mut [bool]) -> f64 {
+        let Some(first) = matched.iter().position(|&taken| !taken) else {
+            return 0.0;
+        };
+
+        matched[first] = true;
+        let mut best = f64::INFINITY;
+        for other in first + 1..costs.len() {
+            if !matched[other] {
+                matched[other] = true;
+                best = best.min(costs[first][other] + get_brute_force_cost(costs, matched));
+                matched[other] = false;
+            }
+        }
+        matched[first] = false;
+
+        best
+    }
+
+    #[test]
+    fn matching_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for count in (2..=12).step_by(2) {
+            for _ in 0..20 {
+                let weights: Vec<f64> = (0..count * count).map(|_| rng.gen_range(0.0..100.0)).collect();
+                let costs: Vec<Vec<f64>> = (0..count)
+                    .map(|i| (0..count).map(|j| weights[i.min(j) * count + i.max(j)]).collect())
+                    .collect();
+
+                let mates = get_min_weight_perfect_matching(count, |i, j| costs[i][j]);
+                for (vertex, &mate) in mates.iter().enumerate() {
+                    assert_ne!(vertex, mate);
+                    assert_eq!(mates[mate], vertex);
+                }
+
+                let cost: f64 = (0..count).filter(|&vertex| vertex < mates[vertex]).map(|vertex| costs[vertex][mates[vertex]]).sum();
+                let optimum = get_brute_force_cost(&costs, &mut vec![false; count]);
+                assert!((cost - optimum).abs() < 1e-4, "matched {} points for {}, the optimum is {}", count, cost, optimum);
+            }
+        }
+    }
+}
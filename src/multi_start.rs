@@ -0,0 +1,87 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::distance_map::DistanceMap;
+use crate::solution::{Incumbent, Solution};
+use crate::get_local_descent;
+
+/* Number of local searches an island runs between migrations. */
+const MIGRATION_INTERVAL: usize = 20;
+
+/* Double bridge kick. Cuts the route into four pieces A B C D and reconnects them as A C B D,
+*  a change 2-opt and Or-opt can't undo in a single move. */
+pub fn double_bridge<R: Rng>(route: &[u32], rng: &mut R) -> Vec<u32> {
+    let count = route.len();
+
+    if count < 8 {
+        let mut shuffled = route.to_vec();
+        shuffled.shuffle(rng);
+        return shuffled;
+    }
+
+    let mut cuts = [rng.gen_range(1..count), rng.gen_range(1..count), rng.gen_range(1..count)];
+    cuts.sort();
+
+    let mut new_route = Vec::with_capacity(count);
+    new_route.extend_from_slice(&route[..cuts[0]]);
+    new_route.extend_from_slice(&route[cuts[1]..cuts[2]]);
+    new_route.extend_from_slice(&route[cuts[0]..cuts[1]]);
+    new_route.extend_from_slice(&route[cuts[2]..]);
+    new_route
+}
+
+/* Parallel multi-start local search. Every rayon thread runs its own restarts with its own
+*  seeded RNG and offers each local minima to the shared best.
+*
+*  Without islands every restart begins from a fresh random tour. With islands each thread
+*  instead kicks its own best tour with a double bridge and descends again, and every
+*  MIGRATION_INTERVAL restarts it sends that best tour to the next island in a ring, which
+*  adopts it if it is shorter than its own. */
+pub fn get_multi_start(map: &DistanceMap, initial: Solution, islands: bool, seed: u64, max_duration: Duration) -> Solution {
+    let deadline = Instant::now() + max_duration;
+    let threads = rayon::current_num_threads();
+    let incumbent = Incumbent::new(initial.clone());
+    let mailboxes: Vec<Mutex<Option<Solution>>> = (0..threads).map(|_| Mutex::new(None)).collect();
+
+    (0..threads).into_par_iter().for_each(|worker| {
+        let mut rng = StdRng::seed_from_u64(seed.wrapping_add(worker as u64));
+        let mut island_best = initial.clone();
+        let mut iteration: usize = 0;
+
+        while Instant::now() < deadline {
+            let start_route = if islands {
+                double_bridge(&island_best.route, &mut rng)
+            } else {
+                let mut route: Vec<u32> = (0..map.point_count() as u32).collect();
+                route.shuffle(&mut rng);
+                route
+            };
+
+            let solution = get_local_descent(map, Solution { route: start_route, distance: 0.0 }, deadline);
+            incumbent.offer(&solution);
+
+            if solution.distance < island_best.distance {
+                island_best = solution;
+            }
+
+            iteration += 1;
+
+            if islands && iteration.is_multiple_of(MIGRATION_INTERVAL) {
+                if let Some(migrant) = mailboxes[worker].lock().unwrap().take() {
+                    if migrant.distance < island_best.distance {
+                        island_best = migrant;
+                    }
+                }
+
+                *mailboxes[(worker + 1) % threads].lock().unwrap() = Some(island_best.clone());
+            }
+        }
+    });
+
+    incumbent.into_solution()
+}
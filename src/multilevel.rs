@@ -0,0 +1,508 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::candidate_graph::CandidateGraph;
+use crate::distance_map::{DistanceMap, EdgeCost, FIXED_EDGE_COST};
+use crate::point::{Point, Points};
+use crate::priority_queue_structs::DisjointSet;
+use crate::query::UserQuery;
+use crate::solution::Solution;
+use crate::get_solution_length;
+
+/* Nearest neighbors each city is matched and refined against. */
+const CANDIDATE_NEIGHBORS: usize = 10;
+
+/* Coarsening stops once the instance is down to this many segments. */
+const COARSEST_SEGMENTS: usize = 16;
+
+/* Gain a move has to beat, so rounding error can't make the refinement cycle. */
+const IMPROVEMENT_EPSILON: f64 = 1e-9;
+
+/* Edges are matched as if up to this much longer, at random, when coarsening a tour again, so
+*  every cycle fixes a different set of them. */
+const MATCHING_NOISE: f64 = 0.5;
+
+/* Distances with the edges fixed by coarsening pinned in place. Each city has at most two. */
+struct FixedEdges<'a> {
+    map: &'a DistanceMap,
+    fixed: Vec<Vec<u32>>,
+}
+
+impl EdgeCost for FixedEdges<'_> {
+    fn get_distance_from_points(&self, point1: &u32, point2: &u32) -> f64 {
+        if self.fixed[*point1 as usize].contains(point2) {
+            return FIXED_EDGE_COST;
+        }
+
+        self.map.get_distance_from_points(point1, point2)
+    }
+}
+
+/* One round of coarsening. Segment ends are matched to the nearest candidate end of another
+*  segment, shortest edges first, and every match is fixed. Each end gets at most one new edge
+*  per round, so the number of segments roughly halves. With `noise`, each edge is taken as up
+*  to that share longer, at random. Returns the edges fixed. */
+fn coarsen(map: &DistanceMap, candidates: &CandidateGraph, fixed: &mut [Vec<u32>], segments: &mut DisjointSet,
+    noise: f64, rng: &mut StdRng) -> Vec<(u32, u32)> {
+    let count = fixed.len() as u32;
+    let roots: Vec<u32> = (0..count).map(|node| segments.find(node)).collect();
+
+    let mut edges: Vec<(f64, u32, u32)> = (0..count).into_par_iter()
+        .filter(|&node| fixed[node as usize].len() < 2)
+        .flat_map_iter(|node| {
+            let (fixed, roots) = (&*fixed, &roots);
+            candidates.get_neighbors(node).iter()
+                .filter(move |&&other| node < other && fixed[other as usize].len() < 2 && roots[node as usize] != roots[other as usize])
+                .map(move |&other| (map.get_distance_from_points(&node, &other), node, other))
+        })
+        .collect();
+    if noise > 0.0 {
+        for edge in edges.iter_mut() {
+            edge.0 *= 1.0 + rng.gen::<f64>() * noise;
+        }
+    }
+    edges.par_sort_unstable_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let mut matched: Vec<bool> = vec![false; count as usize];
+    let mut level: Vec<(u32, u32)> = Vec::new();
+
+    for (_, node1, node2) in edges {
+        if matched[node1 as usize] || matched[node2 as usize] || segments.find(node1) == segments.find(node2) {
+            continue;
+        }
+
+        matched[node1 as usize] = true;
+        matched[node2 as usize] = true;
+        segments.union(node1, node2);
+        fixed[node1 as usize].push(node2);
+        fixed[node2 as usize].push(node1);
+        level.push((node1, node2));
+    }
+
+    level
+}
+
+/* Cities of the segment that starts at `end`, walked to its other end. */
+fn walk_segment(fixed: &[Vec<u32>], end: u32) -> Vec<u32> {
+    let mut segment: Vec<u32> = vec![end];
+    let mut previous = end;
+
+    while let Some(&next) = fixed[*segment.last().unwrap() as usize].iter().find(|&&next| next != previous) {
+        previous = *segment.last().unwrap();
+        segment.push(next);
+    }
+
+    segment
+}
+
+/* Tour of the coarsest level. Each segment comes down to its two ends, joined by an edge fixed
+*  in place, and the query's construction and local descent solve that small instance. The
+*  segments are then walked in the order its tour visits them. */
+fn get_coarsest_tour(map: &DistanceMap, points: &Points, query: &UserQuery, fixed: &[Vec<u32>], seed: u64, deadline: Instant) -> Vec<u32> {
+    let count = fixed.len();
+    let mut covered: Vec<bool> = vec![false; count];
+    let mut segments: Vec<Vec<u32>> = Vec::new();
+
+    for end in (0..count as u32).filter(|&node| fixed[node as usize].len() < 2) {
+        if !covered[end as usize] {
+            let segment = walk_segment(fixed, end);
+            covered[*segment.last().unwrap() as usize] = true;
+            segments.push(segment);
+        }
+    }
+
+    // Cities outside the plane, like the extra city of an open path, go last so the points of
+    // the others line up with their nodes.
+    let mut ends: Vec<u32> = segments.iter()
+        .flat_map(|segment| if segment.len() > 1 { vec![segment[0], *segment.last().unwrap()] } else { vec![segment[0]] })
+        .collect();
+    ends.sort_by_key(|&end| end as usize >= points.points.len());
+
+    let mut index: Vec<usize> = vec![usize::MAX; count];
+    for (node, &end) in ends.iter().enumerate() {
+        index[end as usize] = node;
+    }
+
+    let coarse_map = DistanceMap { map: DashMap::new(), num_points: ends.len() as u32 };
+    for segment in segments.iter().filter(|segment| segment.len() > 1) {
+        let (first, last) = (index[segment[0] as usize], index[*segment.last().unwrap() as usize]);
+        coarse_map.map.insert((first.min(last) as u32, first.max(last) as u32), FIXED_EDGE_COST);
+    }
+    for (i, a) in ends.iter().enumerate() {
+        for (j, b) in ends.iter().enumerate().skip(i + 1) {
+            coarse_map.map.entry((i as u32, j as u32)).or_insert_with(|| map.get_distance_from_points(a, b));
+        }
+    }
+
+    let coarse_points = Points {
+        points: ends.iter().enumerate()
+            .filter(|(_, &end)| (end as usize) < points.points.len())
+            .map(|(node, &end)| Point { id: node as u32, ..points.points[end as usize].clone() })
+            .collect(),
+    };
+
+    let mut coarse = crate::get_construction(&coarse_map, &coarse_points, query, seed);
+
+    // Constructions that only look at the points leave the others out. Insert them where they
+    // add the least.
+    for node in coarse.route.len() as u32..ends.len() as u32 {
+        let route = &coarse.route;
+        let position = (0..route.len())
+            .min_by(|&x, &y| {
+                let added = |i: usize| coarse_map.get_distance_from_points(&route[i], &node)
+                    + coarse_map.get_distance_from_points(&node, &route[(i + 1) % route.len()])
+                    - coarse_map.get_distance_from_points(&route[i], &route[(i + 1) % route.len()]);
+                added(x).partial_cmp(&added(y)).unwrap()
+            })
+            .map_or(0, |i| i + 1);
+        coarse.route.insert(position, node);
+    }
+
+    let coarse = crate::get_local_descent(&coarse_map, coarse, deadline);
+
+    let mut visited: Vec<bool> = vec![false; count];
+    let mut route: Vec<u32> = Vec::with_capacity(count);
+    for end in coarse.route.iter().map(|&node| ends[node as usize]) {
+        if !visited[end as usize] {
+            for city in walk_segment(fixed, end) {
+                visited[city as usize] = true;
+                route.push(city);
+            }
+        }
+    }
+
+    route
+}
+
+/* Reverse the route between positions i and j, wrapping around the end. Reversing the rest of
+*  the tour instead gives the same cycle, so the shorter side is the one reversed. */
+fn reverse(route: &mut [u32], position: &mut [usize], i: usize, j: usize) {
+    let count = route.len();
+    let inside = (j + count - i) % count + 1;
+
+    let (mut i, mut j, steps) = if inside * 2 <= count {
+        (i, j, inside / 2)
+    } else {
+        ((j + 1) % count, (i + count - 1) % count, (count - inside) / 2)
+    };
+
+    for _ in 0..steps {
+        route.swap(i, j);
+        position[route[i] as usize] = i;
+        position[route[j] as usize] = j;
+        i = (i + 1) % count;
+        j = (j + count - 1) % count;
+    }
+}
+
+/* Try 2-opt moves that give `city` one of its candidate neighbors. Returns the cities whose
+*  edges changed. */
+fn try_two_opt<C: EdgeCost>(cost: &C, candidates: &CandidateGraph, route: &mut [u32], position: &mut [usize], city: u32) -> Option<[u32; 4]> {
+    let count = route.len();
+    let a_index = position[city as usize];
+
+    for forward in [true, false] {
+        let b_index = if forward { (a_index + 1) % count } else { (a_index + count - 1) % count };
+        let b = route[b_index];
+        let removed = cost.get_distance_from_points(&city, &b);
+
+        for &c in candidates.get_neighbors(city) {
+            let added = cost.get_distance_from_points(&city, &c);
+            if added >= removed {
+                break;
+            }
+
+            let c_index = position[c as usize];
+            let d_index = if forward { (c_index + 1) % count } else { (c_index + count - 1) % count };
+            let d = route[d_index];
+
+            if c == b || d == city {
+                continue;
+            }
+
+            let delta = added + cost.get_distance_from_points(&b, &d) - removed - cost.get_distance_from_points(&c, &d);
+            if delta < -IMPROVEMENT_EPSILON {
+                // Going forwards b..c is reversed, going backwards c..b is, wrapped either way.
+                if forward {
+                    reverse(route, position, b_index, c_index);
+                } else {
+                    reverse(route, position, c_index, b_index);
+                }
+                return Some([city, b, c, d]);
+            }
+        }
+    }
+
+    None
+}
+
+/* Try moving the segment of up to three cities starting at `city` next to one of its candidate
+*  neighbors, either way round. Returns the cities whose edges changed. */
+fn try_or_opt<C: EdgeCost>(cost: &C, candidates: &CandidateGraph, route: &mut [u32], position: &mut [usize], city: u32) -> Option<[u32; 4]> {
+    let count = route.len();
+    let start = position[city as usize];
+
+    for length in 1..=3 {
+        if count < length + 3 {
+            break;
+        }
+
+        let end = (start + length - 1) % count;
+        let (p, e, n) = (route[(start + count - 1) % count], route[end], route[(end + 1) % count]);
+        let removed = cost.get_distance_from_points(&p, &city) + cost.get_distance_from_points(&e, &n) - cost.get_distance_from_points(&p, &n);
+        let in_segment = |node: u32| (position[node as usize] + count - start) % count < length;
+
+        for &c in candidates.get_neighbors(city) {
+            if in_segment(c) {
+                continue;
+            }
+
+            // Between c and the city after it, or the city before it and c.
+            for (x, y) in [(c, route[(position[c as usize] + 1) % count]), (route[(position[c as usize] + count - 1) % count], c)] {
+                if in_segment(x) || in_segment(y) {
+                    continue;
+                }
+
+                let joined = cost.get_distance_from_points(&x, &y);
+                let forward = cost.get_distance_from_points(&x, &city) + cost.get_distance_from_points(&e, &y) - joined;
+                let reversed = cost.get_distance_from_points(&x, &e) + cost.get_distance_from_points(&city, &y) - joined;
+
+                if forward.min(reversed) - removed < -IMPROVEMENT_EPSILON {
+                    // Rotate the segment to the front, then move it to just after x.
+                    route.rotate_left(start);
+                    let target = (position[x as usize] + count - start) % count;
+                    route[..=target].rotate_left(length);
+                    if reversed < forward {
+                        route[target + 1 - length..=target].reverse();
+                    }
+
+                    for (index, node) in route.iter().enumerate() {
+                        position[*node as usize] = index;
+                    }
+                    return Some([p, n, x, y]);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/* 2-opt and Or-opt over the candidate graph, driven by don't-look bits like `local_descent`. */
+fn refine<C: EdgeCost>(cost: &C, candidates: &CandidateGraph, route: &mut [u32], deadline: Instant) {
+    let count = route.len();
+
+    if count < 5 {
+        return;
+    }
+
+    let mut position: Vec<usize> = vec![0; count];
+    for (index, node) in route.iter().enumerate() {
+        position[*node as usize] = index;
+    }
+
+    let mut active: VecDeque<u32> = route.iter().copied().collect();
+    let mut queued: Vec<bool> = vec![true; count];
+
+    while let Some(city) = active.pop_front() {
+        if Instant::now() >= deadline {
+            return;
+        }
+        queued[city as usize] = false;
+
+        let changed = try_two_opt(cost, candidates, route, &mut position, city)
+            .or_else(|| try_or_opt(cost, candidates, route, &mut position, city));
+
+        if let Some(cities) = changed {
+            for node in cities.into_iter().chain([city]) {
+                if !queued[node as usize] {
+                    queued[node as usize] = true;
+                    active.push_back(node);
+                }
+            }
+        }
+    }
+}
+
+/* Where a cycle starts from once it has coarsened the instance. */
+enum CycleStart<'a> {
+    /* A fresh tour of the coarsest level, from the query's construction on its points. */
+    Construct(&'a Points, &'a UserQuery),
+    /* A tour holding every edge the matching offers, which the tour it was built from does.
+    *  Refining can only shorten it. */
+    Tour(Vec<u32>),
+}
+
+/* One multilevel cycle. Coarsens over `matching` level after level until only a few segments
+*  are left, then undoes every level in turn, freeing its edges and refining the whole tour with
+*  2-opt and Or-opt while the edges of the coarser levels stay fixed. */
+fn run_cycle(map: &DistanceMap, candidates: &CandidateGraph, matching: &CandidateGraph, start: CycleStart,
+    noise: f64, rng: &mut StdRng, deadline: Instant) -> Vec<u32> {
+    let count = map.point_count();
+
+    let mut fixed: Vec<Vec<u32>> = vec![Vec::with_capacity(2); count];
+    let mut segments = DisjointSet::new(count);
+    let mut levels: Vec<Vec<(u32, u32)>> = Vec::new();
+    let mut segment_count = count;
+
+    while segment_count > COARSEST_SEGMENTS {
+        let level = coarsen(map, matching, &mut fixed, &mut segments, noise, rng);
+        if level.is_empty() {
+            break;
+        }
+
+        segment_count -= level.len();
+        levels.push(level);
+    }
+
+    let mut route = match start {
+        CycleStart::Construct(points, query) => {
+            println!("Coarsened {} cities into {} segments over {} levels.", count, segment_count, levels.len());
+            get_coarsest_tour(map, points, query, &fixed, rng.gen(), deadline)
+        }
+        CycleStart::Tour(route) => route,
+    };
+    let mut cost = FixedEdges { map, fixed };
+
+    refine(&cost, candidates, &mut route, deadline);
+
+    while let Some(level) = levels.pop() {
+        for (node1, node2) in level {
+            cost.fixed[node1 as usize].retain(|&node| node != node2);
+            cost.fixed[node2 as usize].retain(|&node| node != node1);
+        }
+
+        refine(&cost, candidates, &mut route, deadline);
+    }
+
+    route
+}
+
+/* Multilevel solver after Walshaw. The first cycle coarsens by matching nearby segment ends over
+*  the candidate graph, joins the last few segments into a tour with the query's construction
+*  and refines it level by level, so each level only has to repair what the last matching got
+*  wrong. `points` are those of the map's cities, which may have one more outside the plane.
+*
+*  Walshaw's iterated variant then spends the rest of the time on further cycles from the best
+*  tour, `initial` if it is shorter. These only match edges of that tour, in a perturbed order,
+*  so every cycle fixes different parts of it and refines the rest around them. */
+pub fn get_multilevel(map: &DistanceMap, points: &Points, query: &UserQuery, initial: &Solution, seed: u64, max_duration: Duration) -> Solution {
+    let deadline = Instant::now() + max_duration;
+    let count = map.point_count();
+
+    if count < 5 {
+        let route: Vec<u32> = (0..count as u32).collect();
+        let distance = get_solution_length(map, &route).0;
+        return Solution { route, distance };
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let candidates = CandidateGraph::new(map, CANDIDATE_NEIGHBORS);
+
+    let route = run_cycle(map, &candidates, &candidates, CycleStart::Construct(points, query), 0.0, &mut rng, deadline);
+    let mut best = Solution { distance: get_solution_length(map, &route).0, route };
+    if initial.route.len() == count && initial.distance < best.distance {
+        best = initial.clone();
+    }
+
+    let mut cycles = 1;
+    while Instant::now() < deadline {
+        let matching = CandidateGraph::from_tour(map, &best.route);
+        let route = run_cycle(map, &candidates, &matching, CycleStart::Tour(best.route.clone()), MATCHING_NOISE, &mut rng, deadline);
+        let distance = get_solution_length(map, &route).0;
+
+        if distance < best.distance {
+            best = Solution { route, distance };
+        }
+        cycles += 1;
+    }
+    println!("Ran {} multilevel cycles.", cycles);
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::Construction;
+    use crate::tests::get_random_points;
+    use rand::seq::SliceRandom;
+
+    #[test]
+    fn candidate_moves_shorten_the_recomputed_length() {
+        for seed in 0..4 {
+            let map = DistanceMap::new(&get_random_points(80, seed));
+            let candidates = CandidateGraph::new(&map, CANDIDATE_NEIGHBORS);
+
+            let mut route: Vec<u32> = (0..80).collect();
+            route.shuffle(&mut StdRng::seed_from_u64(seed));
+            let mut position: Vec<usize> = vec![0; route.len()];
+            for (index, node) in route.iter().enumerate() {
+                position[*node as usize] = index;
+            }
+
+            let mut length = get_solution_length(&map, &route).0;
+            let mut moves = 0;
+            for pass in 0..10 {
+                for city in 0..80 {
+                    let changed = if pass % 2 == 0 {
+                        try_two_opt(&map, &candidates, &mut route, &mut position, city)
+                    } else {
+                        try_or_opt(&map, &candidates, &mut route, &mut position, city)
+                    };
+
+                    if changed.is_some() {
+                        let new_length = get_solution_length(&map, &route).0;
+                        assert!(new_length < length, "a move lengthened the tour from {} to {}", length, new_length);
+                        length = new_length;
+                        moves += 1;
+                    }
+                    for (index, node) in route.iter().enumerate() {
+                        assert_eq!(position[*node as usize], index);
+                    }
+                }
+            }
+
+            assert!(moves > 0);
+            let mut sorted = route.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, (0..80).collect::<Vec<u32>>());
+        }
+    }
+
+    #[test]
+    fn multilevel_never_returns_worse_than_its_start() {
+        let points = get_random_points(200, 0);
+        let map = DistanceMap::new(&points);
+        let initial = crate::get_two_opt(&map, crate::get_random_solution(&map));
+        let solution = get_multilevel(&map, &points, &UserQuery::new(), &initial, 0, Duration::from_millis(300));
+
+        assert!(solution.distance <= initial.distance + 1e-6);
+        assert!((get_solution_length(&map, &solution.route).0 - solution.distance).abs() < 1e-6);
+    }
+
+    /* The map may hold a city outside the plane, which constructions from the points can't see. */
+    #[test]
+    fn coarsest_tour_visits_every_city() {
+        let points = get_random_points(200, 1);
+        let mut map = DistanceMap::new(&points);
+        map.add_point(&vec![0.0; 200]);
+
+        for construction in [Construction::NearestNeighbor, Construction::Hilbert, Construction::GreedyEdge] {
+            let mut query = UserQuery::new();
+            query.construction = construction;
+
+            let initial = Solution { route: Vec::new(), distance: f64::INFINITY };
+            let solution = get_multilevel(&map, &points, &query, &initial, 0, Duration::from_millis(100));
+
+            let mut route = solution.route.clone();
+            route.sort_unstable();
+            assert_eq!(route, (0..201).collect::<Vec<u32>>());
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use rand::SeedableRng;
+use rayon::prelude::*;
+
+use crate::distance_map::DistanceMap;
+use crate::solution::Solution;
+
+/* Nearest neighbor tour from the given start, read from a dense matrix. Runs on one thread,
+*  since the callers already run one start per thread. */
+pub fn get_nearest_neighbor_from(matrix: &[Vec<f64>], start: u32) -> Solution {
+    let count = matrix.len();
+    let mut visited: Vec<bool> = vec![false; count];
+    let mut route: Vec<u32> = Vec::with_capacity(count);
+    let mut distance: f64 = 0.0;
+
+    let mut current = start as usize;
+    visited[current] = true;
+    route.push(start);
+
+    while route.len() < count {
+        let (next, step) = (0..count)
+            .filter(|&node| !visited[node])
+            .map(|node| (node, matrix[current][node]))
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .unwrap();
+
+        visited[next] = true;
+        route.push(next as u32);
+        distance += step;
+        current = next;
+    }
+
+    distance += matrix[current][start as usize];
+
+    Solution { route, distance }
+}
+
+/* Nearest neighbor from every city, or from `starts` cities sampled at random, one start per
+*  thread. Returns the `keep` shortest distinct tours, shortest first. Every start costs O(n^2),
+*  so sampling is worth it on large instances. */
+pub fn get_nearest_neighbor_tours(map: &DistanceMap, starts: Option<usize>, keep: usize, seed: u64) -> Vec<Solution> {
+    let count = map.point_count();
+
+    let start_nodes: Vec<u32> = match starts {
+        Some(samples) if samples < count => {
+            let mut rng = StdRng::seed_from_u64(seed);
+            sample(&mut rng, count, samples.max(1)).into_iter().map(|node| node as u32).collect()
+        }
+        _ => (0..count as u32).collect(),
+    };
+
+    let matrix = map.get_matrix();
+
+    let mut tours: Vec<Solution> = start_nodes.into_par_iter()
+        .map(|start| get_nearest_neighbor_from(&matrix, start))
+        .collect();
+    tours.par_sort_unstable_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+
+    // Starts on the same cycle build it again from another city, so equal lengths are kept once.
+    tours.dedup_by(|a, b| (a.distance - b.distance).abs() < 1e-9);
+    tours.truncate(keep.max(1));
+
+    tours
+}
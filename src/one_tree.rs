@@ -0,0 +1,174 @@
+use crate::distance_map::DistanceMap;
+
+/* Subgradient iterations without improvement before the step size is halved. */
+const PATIENCE: usize = 10;
+
+/* Slack subtracted from every bound so floating point error never prunes an optimal route. */
+const EPSILON: f64 = 1e-7;
+
+/* Held-Karp lower bound. Node penalties pi are added to every edge touching a node,
+*  c'(i, j) = c(i, j) + pi[i] + pi[j], which leaves the order of all tours unchanged since every
+*  tour visits each node exactly once. The minimum 1-tree under c', minus 2 * sum(pi), is then a
+*  lower bound on the optimal tour, and subgradient optimization tunes pi to push it up. */
+pub struct OneTreeBound {
+    matrix: Vec<Vec<f64>>,
+    penalties: Vec<f64>,
+    pub bound: f64,
+}
+
+impl OneTreeBound {
+    /* Runs up to `max_iterations` subgradient steps. `upper_bound` is the length of any known
+    *  tour and only scales the step size. */
+    pub fn new(map: &DistanceMap, upper_bound: f64, max_iterations: usize) -> OneTreeBound {
+        let matrix = map.get_matrix();
+        let count = matrix.len();
+
+        let mut one_tree_bound = OneTreeBound { matrix, penalties: vec![0.0; count], bound: 0.0 };
+
+        if count < 3 {
+            one_tree_bound.bound = crate::round(upper_bound);
+            return one_tree_bound;
+        }
+
+        let mut penalties: Vec<f64> = vec![0.0; count];
+        let mut best_bound = f64::MIN;
+        let mut scale = 2.0;
+        let mut stalled = 0;
+
+        for _ in 0..max_iterations {
+            let (weight, degrees) = one_tree_bound.get_one_tree(&penalties);
+            let bound = weight - 2.0 * penalties.iter().sum::<f64>();
+
+            if bound > best_bound {
+                best_bound = bound;
+                one_tree_bound.penalties = penalties.clone();
+                stalled = 0;
+            } else {
+                stalled += 1;
+                if stalled >= PATIENCE {
+                    scale /= 2.0;
+                    stalled = 0;
+                }
+            }
+
+            // Every node has degree two, so the 1-tree is a tour and the bound is tight.
+            let norm: f64 = degrees.iter().map(|&degree| (degree as f64 - 2.0).powi(2)).sum();
+            if norm == 0.0 || scale < 1e-6 || bound >= upper_bound {
+                break;
+            }
+
+            let step = scale * (upper_bound - bound).max(1e-3) / norm;
+            for (penalty, degree) in penalties.iter_mut().zip(degrees.iter()) {
+                *penalty += step * (*degree as f64 - 2.0);
+            }
+        }
+
+        one_tree_bound.bound = best_bound.min(upper_bound) - EPSILON;
+        one_tree_bound
+    }
+
+    fn get_cost(&self, penalties: &[f64], i: usize, j: usize) -> f64 {
+        self.matrix[i][j] + penalties[i] + penalties[j]
+    }
+
+    /* Minimum spanning tree over nodes 1..n with Prim's algorithm, plus the two cheapest edges
+    *  from node zero. Returns the penalized weight and the degree of every node. */
+    fn get_one_tree(&self, penalties: &[f64]) -> (f64, Vec<u32>) {
+        let count = self.matrix.len();
+        let mut degrees: Vec<u32> = vec![0; count];
+        let mut in_tree: Vec<bool> = vec![false; count];
+        let mut closest: Vec<(f64, usize)> = (0..count).map(|j| (self.get_cost(penalties, 1, j), 1)).collect();
+        let mut weight = 0.0;
+
+        in_tree[0] = true;
+        in_tree[1] = true;
+
+        for _ in 2..count {
+            let next = (0..count)
+                .filter(|&j| !in_tree[j])
+                .min_by(|&a, &b| closest[a].0.partial_cmp(&closest[b].0).unwrap())
+                .unwrap();
+
+            weight += closest[next].0;
+            degrees[next] += 1;
+            degrees[closest[next].1] += 1;
+            in_tree[next] = true;
+
+            for j in (0..count).filter(|&j| !in_tree[j]) {
+                let cost = self.get_cost(penalties, next, j);
+                if cost < closest[j].0 {
+                    closest[j] = (cost, next);
+                }
+            }
+        }
+
+        let mut special: Vec<(f64, usize)> = (1..count).map(|j| (self.get_cost(penalties, 0, j), j)).collect();
+        special.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        for (cost, j) in special.iter().take(2) {
+            weight += cost;
+            degrees[*j] += 1;
+            degrees[0] += 1;
+        }
+
+        (weight, degrees)
+    }
+
+    /* Lower bound on the cost of finishing a partial route that runs from `first` to `last`:
+    *  every completion is a path from `last` through all of `unvisited` and back to `first`,
+    *  which costs at least a spanning tree over `unvisited` plus the cheapest edge from each end.
+    *  The penalties carry over, with the unvisited nodes at degree two and the ends at one. */
+    pub fn get_remaining_bound(&self, first: u32, last: u32, unvisited: &[u32]) -> f64 {
+        let (first, last) = (first as usize, last as usize);
+        let penalties = &self.penalties;
+
+        if unvisited.is_empty() {
+            return self.matrix[last][first];
+        }
+
+        let mut weight = 0.0;
+        let mut in_tree: Vec<bool> = vec![false; unvisited.len()];
+        let mut closest: Vec<f64> = unvisited.iter()
+            .map(|&j| self.get_cost(penalties, unvisited[0] as usize, j as usize))
+            .collect();
+        in_tree[0] = true;
+
+        for _ in 1..unvisited.len() {
+            let next = (0..unvisited.len())
+                .filter(|&j| !in_tree[j])
+                .min_by(|&a, &b| closest[a].partial_cmp(&closest[b]).unwrap())
+                .unwrap();
+
+            weight += closest[next];
+            in_tree[next] = true;
+
+            for j in (0..unvisited.len()).filter(|&j| !in_tree[j]) {
+                let cost = self.get_cost(penalties, unvisited[next] as usize, unvisited[j] as usize);
+                if cost < closest[j] {
+                    closest[j] = cost;
+                }
+            }
+        }
+
+        let cheapest_from = |end: usize| -> f64 {
+            unvisited.iter()
+                .map(|&j| self.get_cost(penalties, end, j as usize))
+                .fold(f64::MAX, f64::min)
+        };
+
+        weight += cheapest_from(first) + cheapest_from(last);
+
+        let penalty_sum: f64 = unvisited.iter().map(|&j| penalties[j as usize]).sum();
+
+        weight - 2.0 * penalty_sum - penalties[first] - penalties[last] - EPSILON
+    }
+
+    /* Relative optimality gap of a tour of the given length, e.g. 0.01 is within 1% of optimal. */
+    pub fn get_gap(&self, distance: f64) -> f64 {
+        if self.bound <= 0.0 {
+            return 0.0;
+        }
+
+        ((distance - self.bound) / self.bound).max(0.0)
+    }
+}
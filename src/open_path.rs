@@ -0,0 +1,114 @@
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::distance_map::DistanceMap;
+use crate::point::Points;
+use crate::solution::{SearchReport, Solution};
+use crate::get_solution_length;
+
+/* Ends of an open path, as written to the solution file. None is an end left free. */
+#[derive(Clone, Debug, Serialize)]
+pub struct PathEnds {
+    pub start: Option<u32>,
+    pub end: Option<u32>,
+}
+
+/* A shortest Hamiltonian path, solved as a tour through one extra city the path leaves from and
+*  returns to, so every tour solver handles it unchanged. The extra city's edges to the allowed
+*  ends of the path cost nothing and the rest cost `detour`, then the tour is cut open there.
+*
+*  A tour that avoids a fixed end can always be rerouted to use it for at most one more edge, so
+*  any detour longer than the longest edge makes the fixed ends the cheapest way in and out. */
+pub struct OpenPath {
+    pub ends: PathEnds,
+    depot: u32,
+    detour: f64,
+}
+
+impl OpenPath {
+    /* Add the extra city to the map. The fixed ends must be cities of the map. */
+    pub fn new(map: &mut DistanceMap, points: &Points, ends: PathEnds) -> OpenPath {
+        let count = map.point_count();
+
+        let (min_x, max_x, min_y, max_y) = points.points.par_iter()
+            .map(|point| (point.x, point.x, point.y, point.y))
+            .reduce(|| (f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY, f64::NEG_INFINITY),
+                |a, b| (a.0.min(b.0), a.1.max(b.1), a.2.min(b.2), a.3.max(b.3)));
+        // No edge is longer than the diagonal of the bounding box.
+        let detour = 2.0 * ((max_x - min_x).powi(2) + (max_y - min_y).powi(2)).sqrt();
+
+        let free = ends.start.is_none() && ends.end.is_none();
+        let distances: Vec<f64> = (0..count as u32)
+            .map(|node| if free || ends.start == Some(node) || ends.end == Some(node) { 0.0 } else { detour })
+            .collect();
+        map.add_point(&distances);
+
+        OpenPath { ends, depot: count as u32, detour }
+    }
+
+    /* What the tour pays for its two edges at the extra city, on top of the path. */
+    pub fn get_offset(&self) -> f64 {
+        if self.ends.start.is_some() != self.ends.end.is_some() { self.detour } else { 0.0 }
+    }
+
+    /* Constructions that only look at the points leave the extra city out. Insert it where it
+    *  adds the least. */
+    pub fn add_to_tour(&self, map: &DistanceMap, solution: Solution) -> Solution {
+        if solution.route.contains(&self.depot) {
+            return solution;
+        }
+
+        let route = &solution.route;
+        let (position, added) = (0..route.len())
+            .map(|i| {
+                let (a, b) = (route[i], route[(i + 1) % route.len()]);
+                (i + 1, map.get_distance_from_points(&a, &self.depot) + map.get_distance_from_points(&self.depot, &b)
+                    - map.get_distance_from_points(&a, &b))
+            })
+            .min_by(|x, y| x.1.partial_cmp(&y.1).unwrap())
+            .unwrap();
+
+        let mut route = solution.route;
+        route.insert(position, self.depot);
+
+        Solution { route, distance: solution.distance + added }
+    }
+
+    /* Cut the tour open at the extra city, and turn it to run from the fixed start or to the
+    *  fixed end. */
+    pub fn get_path(&self, map: &DistanceMap, solution: &Solution) -> Solution {
+        let position = solution.route.iter().position(|&node| node == self.depot).unwrap();
+
+        let mut route: Vec<u32> = solution.route[position + 1..].to_vec();
+        route.extend_from_slice(&solution.route[..position]);
+
+        let reversed = match (self.ends.start, self.ends.end) {
+            (Some(start), _) => route.first() != Some(&start),
+            (None, Some(end)) => route.last() != Some(&end),
+            (None, None) => false,
+        };
+        if reversed {
+            route.reverse();
+        }
+
+        // The path holds one city less than the map, so it isn't measured as a closed tour.
+        let distance = get_solution_length(map, &route).0;
+
+        Solution { route, distance }
+    }
+
+    /* Bring a report on the tour over to `path`, the tour cut open. */
+    pub fn get_path_report(&self, report: &SearchReport, path: &Solution) -> SearchReport {
+        let lower_bound = if report.proven_optimal {
+            path.distance
+        } else {
+            (report.lower_bound - self.get_offset()).min(path.distance)
+        };
+
+        SearchReport {
+            lower_bound,
+            gap: if lower_bound > 0.0 { (path.distance - lower_bound) / lower_bound } else { 0.0 },
+            ..report.clone()
+        }
+    }
+}
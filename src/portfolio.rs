@@ -0,0 +1,99 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::distance_map::DistanceMap;
+use crate::grasp::get_grasp;
+use crate::guided_local_search::get_guided_local_search;
+use crate::multi_start::get_multi_start;
+use crate::multilevel::get_multilevel;
+use crate::point::Points;
+use crate::query::{LocalMethod, UserQuery};
+use crate::solution::{Incumbent, Solution};
+use crate::{get_local_descent, get_random_solution};
+
+/* Run one solver of the portfolio from the shared best tour until `max_duration` has passed,
+*  with the options of the query. */
+fn run_solver(map: &DistanceMap, points: &Points, query: &UserQuery, method: LocalMethod, initial: Solution, seed: u64, max_duration: Duration) -> Solution {
+    match method {
+        LocalMethod::GuidedLocalSearch => get_guided_local_search(map, initial, max_duration),
+        // Relinking towards the shared tour is how GRASP builds on it, so it is always on here.
+        LocalMethod::Grasp => get_grasp(map, &initial, query.grasp_alpha, true, seed, max_duration),
+        LocalMethod::MultiStart => get_multi_start(map, initial, query.islands, seed, max_duration),
+        LocalMethod::Multilevel => get_multilevel(map, points, query, &initial, seed, max_duration),
+        LocalMethod::TwoOpt => {
+            let deadline = Instant::now() + max_duration;
+            let mut best = initial;
+
+            while Instant::now() < deadline {
+                let solution = get_local_descent(map, get_random_solution(map), deadline);
+                if solution.distance < best.distance {
+                    best = solution;
+                }
+            }
+
+            best
+        }
+        LocalMethod::Decomposition | LocalMethod::Portfolio => initial,
+    }
+}
+
+/* Race the query's portfolio of solvers against each other, each on its own rayon pool with an
+*  equal share of the threads. The time is split into the query's number of rounds. A solver
+*  only hands in its best tour at the end of a round, and every round restarts them all from the
+*  best so far, so an improvement reaches the others at the next round and not before. More
+*  rounds share sooner but cut every run shorter.
+*
+*  Returns the best tour and the solver that found it, or None if none of them beat `initial`. */
+pub fn get_portfolio(map: &DistanceMap, points: &Points, initial: Solution, query: &UserQuery, seed: u64, max_duration: Duration) -> (Solution, Option<LocalMethod>) {
+    let deadline = Instant::now() + max_duration;
+    let solvers = &query.portfolio;
+
+    let threads = (rayon::current_num_threads() / solvers.len().max(1)).max(1);
+    let pools: Vec<ThreadPool> = solvers.iter()
+        .map(|_| ThreadPoolBuilder::new().num_threads(threads).build().expect("Failed to build a thread pool"))
+        .collect();
+    println!("Running {} solvers with {} threads each.", solvers.len(), threads);
+
+    let incumbent = Incumbent::new(initial);
+    let producer: Mutex<Option<LocalMethod>> = Mutex::new(None);
+
+    let rounds = query.portfolio_rounds.max(1);
+    for round in 0..rounds {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let slice = remaining / (rounds - round);
+        let start = incumbent.get_solution();
+
+        let results: Vec<f64> = std::thread::scope(|scope| {
+            let handles: Vec<_> = solvers.iter().zip(pools.iter()).enumerate().map(|(index, (&method, pool))| {
+                let (start, incumbent, producer) = (start.clone(), &incumbent, &producer);
+                let seed = seed.wrapping_add((round as usize * solvers.len() + index) as u64);
+
+                scope.spawn(move || {
+                    let solution = pool.install(|| run_solver(map, points, query, method, start, seed, slice));
+
+                    // The producer is only written with the incumbent, so the two always agree.
+                    let mut producer_guard = producer.lock().unwrap();
+                    if solution.distance < incumbent.get_distance() {
+                        incumbent.offer(&solution);
+                        *producer_guard = Some(method);
+                    }
+
+                    solution.distance
+                })
+            }).collect();
+
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        print!("Round {}:", round + 1);
+        for (method, distance) in solvers.iter().zip(results) {
+            print!(" {:?} {:.2}", method, distance);
+        }
+        println!();
+    }
+
+    let producer = producer.into_inner().unwrap();
+    (incumbent.into_solution(), producer)
+}
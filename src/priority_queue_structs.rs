@@ -1,4 +1,5 @@
 use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 
 pub struct Edge {
@@ -33,7 +34,7 @@ impl DisjointSet {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Branch {
     pub route: Vec<u32>,
     pub total_distance: f64,
@@ -50,7 +51,8 @@ impl Eq for Branch {}
 
 impl PartialOrd for Branch {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        OrderedFloat(self.total_priority()).partial_cmp(&OrderedFloat(other.total_priority()))
+        // BinaryHeap compares with these operators, so they have to agree with cmp.
+        Some(self.cmp(other))
     }
 }
 
@@ -65,4 +67,25 @@ impl Branch {
     fn total_priority(&self) -> f64 {
         self.total_distance + self.heuristic_estimate
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BinaryHeap;
+
+    #[test]
+    fn queue_pops_lowest_priority_first() {
+        let bounds = [5.0, 1.0, 9.0, 3.0, 7.0, 2.0];
+        let mut queue: BinaryHeap<Branch> = bounds.iter()
+            .map(|&bound| Branch { route: vec![0], total_distance: bound / 2.0, heuristic_estimate: bound / 2.0 })
+            .collect();
+
+        let mut popped: Vec<f64> = Vec::new();
+        while let Some(branch) = queue.pop() {
+            popped.push(branch.total_priority());
+        }
+
+        assert_eq!(popped, vec![1.0, 2.0, 3.0, 5.0, 7.0, 9.0]);
+    }
 }
\ No newline at end of file
@@ -1,8 +1,49 @@
+use serde::{Deserialize, Serialize};
+
 #[derive(PartialEq, Debug)]
 pub enum Usage {
     Generate,
     SolveOptimal,
     SolveLocal,
+    Bound,
+    Resume,
+    Auto,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum LocalMethod {
+    TwoOpt,
+    GuidedLocalSearch,
+    Grasp,
+    MultiStart,
+    Decomposition,
+    Multilevel,
+    Portfolio,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Construction {
+    NearestNeighbor,
+    Christofides,
+    GreedyEdge,
+    NearestInsertion,
+    FarthestInsertion,
+    CheapestInsertion,
+    RandomInsertion,
+    ConvexHullInsertion,
+    Hilbert,
+    Sierpinski,
+    Savings,
+    MultiStartNearestNeighbor,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum OptimalMethod {
+    HeldKarp,
+    BranchAndBound,
+    BestFirst,
+    ReducedMatrix,
+    CuttingPlane,
 }
 
 pub struct UserQuery {
@@ -11,10 +52,44 @@ pub struct UserQuery {
     pub filename:String,
     pub time:u32,
     pub max_points:u32,
+    pub local_method:LocalMethod,
+    pub grasp_alpha:f64,
+    pub path_relinking:bool,
+    pub islands:bool,
+    pub seed:Option<u64>,
+    pub optimal_method:OptimalMethod,
+    pub memory_limit:u64,
+    pub solution_filename:Option<String>,
+    pub report_gap:bool,
+    pub time_limit:Option<u64>,
+    pub node_limit:Option<u64>,
+    pub checkpoint_filename:Option<String>,
+    pub construction:Construction,
+    pub depot:Option<u32>,
+    pub starts:Option<usize>,
+    pub pool_size:usize,
+    pub cell_size:usize,
+    pub merge_tours:bool,
+    pub window_size:Option<usize>,
+    pub portfolio:Vec<LocalMethod>,
+    pub portfolio_rounds:u32,
+    pub open_path:bool,
+    pub path_start:Option<u32>,
+    pub path_end:Option<u32>,
 }
 
 impl UserQuery {
     pub fn new() -> UserQuery {
-        UserQuery {usage: Usage::SolveLocal, points: 0, filename: String::from("points.json"), time: 60 as u32, max_points: 1000000000 }
+        UserQuery {usage: Usage::SolveLocal, points: 0, filename: String::from("points.json"), time: 60 as u32, max_points: 1000000000,
+            local_method: LocalMethod::TwoOpt, grasp_alpha: 0.2, path_relinking: false,
+            islands: false, seed: None, optimal_method: OptimalMethod::HeldKarp, memory_limit: 4096 * 1024 * 1024,
+            solution_filename: None, report_gap: false, time_limit: None, node_limit: None,
+            checkpoint_filename: None, construction: Construction::NearestNeighbor,
+            depot: None, starts: None, pool_size: 1,
+            cell_size: 200, merge_tours: false,
+            window_size: None,
+            portfolio: vec![LocalMethod::GuidedLocalSearch, LocalMethod::MultiStart, LocalMethod::Grasp, LocalMethod::Multilevel],
+            portfolio_rounds: 4,
+            open_path: false, path_start: None, path_end: None }
     }
 }
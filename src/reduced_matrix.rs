@@ -0,0 +1,232 @@
+use crate::distance_map::DistanceMap;
+use crate::search_limits::SearchStats;
+use crate::solution::{Incumbent, Solution};
+
+/* Depth down to which both children of a node are searched in parallel. */
+const PARALLEL_DEPTH: usize = 8;
+
+/* Slack on pruning, so the rounding of the bound's many subtractions can't keep a node whose
+*  tours are no shorter than bssf alive. */
+const EPSILON: f64 = 1e-7;
+
+/* Search node of Little's algorithm. The matrix is kept reduced, so every active row and column
+*  holds a zero and `bound` is the sum of everything subtracted so far plus the included edges. */
+#[derive(Clone)]
+struct ReducedNode {
+    matrix: Vec<f64>,
+    bound: f64,
+    next: Vec<usize>,
+    has_in: Vec<bool>,
+    start_of: Vec<usize>,
+    end_of: Vec<usize>,
+    edges: usize,
+}
+
+impl ReducedNode {
+    fn new(matrix: &[Vec<f64>]) -> ReducedNode {
+        let count = matrix.len();
+        let mut flat: Vec<f64> = Vec::with_capacity(count * count);
+
+        for (i, row) in matrix.iter().enumerate() {
+            for (j, distance) in row.iter().enumerate() {
+                flat.push(if i == j { f64::INFINITY } else { *distance });
+            }
+        }
+
+        let mut node = ReducedNode {
+            matrix: flat,
+            bound: 0.0,
+            next: vec![usize::MAX; count],
+            has_in: vec![false; count],
+            start_of: (0..count).collect(),
+            end_of: (0..count).collect(),
+            edges: 0,
+        };
+        node.reduce();
+        node
+    }
+
+    fn count(&self) -> usize {
+        self.next.len()
+    }
+
+    /* Subtract the minimum of every active row, then of every active column, and add the total
+    *  to the bound. A row or column with nothing left to subtract makes the node infeasible. */
+    fn reduce(&mut self) {
+        let count = self.count();
+
+        for i in (0..count).filter(|&i| self.next[i] == usize::MAX) {
+            let row = &mut self.matrix[i * count..(i + 1) * count];
+            let min = row.iter().cloned().fold(f64::INFINITY, f64::min);
+
+            if min == f64::INFINITY {
+                self.bound = f64::INFINITY;
+                return;
+            }
+
+            row.iter_mut().for_each(|value| *value -= min);
+            self.bound += min;
+        }
+
+        for j in (0..count).filter(|&j| !self.has_in[j]) {
+            let min = (0..count).map(|i| self.matrix[i * count + j]).fold(f64::INFINITY, f64::min);
+
+            if min == f64::INFINITY {
+                self.bound = f64::INFINITY;
+                return;
+            }
+
+            (0..count).for_each(|i| self.matrix[i * count + j] -= min);
+            self.bound += min;
+        }
+    }
+
+    /* Pick the zero entry whose exclusion would raise the bound the most. Returns the edge and
+    *  that raise, the smallest other entry in its row plus the smallest other in its column. */
+    fn select_edge(&self) -> Option<(usize, usize, f64)> {
+        let count = self.count();
+        let mut best: Option<(usize, usize, f64)> = None;
+
+        for i in (0..count).filter(|&i| self.next[i] == usize::MAX) {
+            for j in (0..count).filter(|&j| !self.has_in[j] && self.matrix[i * count + j] == 0.0) {
+                let row_min = (0..count)
+                    .filter(|&k| k != j)
+                    .map(|k| self.matrix[i * count + k])
+                    .fold(f64::INFINITY, f64::min);
+                let column_min = (0..count)
+                    .filter(|&k| k != i)
+                    .map(|k| self.matrix[k * count + j])
+                    .fold(f64::INFINITY, f64::min);
+                let penalty = row_min + column_min;
+
+                if best.is_none_or(|(_, _, best_penalty)| penalty > best_penalty) {
+                    best = Some((i, j, penalty));
+                }
+            }
+        }
+
+        best
+    }
+
+    /* Fix edge i -> j in the tour. Removes row i and column j, and forbids the edge that would
+    *  close the merged path into a cycle before every node is on it. */
+    fn include(&mut self, i: usize, j: usize) {
+        let count = self.count();
+
+        self.bound += self.matrix[i * count + j];
+        self.next[i] = j;
+        self.has_in[j] = true;
+        self.edges += 1;
+
+        for k in 0..count {
+            self.matrix[i * count + k] = f64::INFINITY;
+            self.matrix[k * count + j] = f64::INFINITY;
+        }
+
+        let start = self.start_of[i];
+        let end = self.end_of[j];
+        self.end_of[start] = end;
+        self.start_of[end] = start;
+
+        if self.edges < count - 1 {
+            self.matrix[end * count + start] = f64::INFINITY;
+        }
+
+        self.reduce();
+    }
+
+    /* Forbid edge i -> j, then restore the zeros its row and column lost. */
+    fn exclude(&mut self, i: usize, j: usize) {
+        let count = self.count();
+        self.matrix[i * count + j] = f64::INFINITY;
+        self.reduce();
+    }
+
+    fn get_route(&self) -> Vec<u32> {
+        let mut route: Vec<u32> = Vec::with_capacity(self.count());
+        let mut node = 0;
+
+        while route.len() < self.count() {
+            route.push(node as u32);
+            node = self.next[node];
+        }
+
+        route
+    }
+}
+
+/* Recursive include/exclude search. Includes are explored first since they dive towards
+*  complete tours, and nodes are pruned once their bound reaches bssf. */
+fn reduced_matrix_recurse(node: ReducedNode,
+    matrix: &[Vec<f64>],
+    incumbent: &Incumbent,
+    stats: &SearchStats,
+    depth: usize,) {
+
+    if !stats.visit() {
+        return;
+    }
+
+    if node.bound >= incumbent.get_distance() - EPSILON {
+        stats.prune();
+        return;
+    }
+
+    if node.edges == node.count() {
+        let route = node.get_route();
+        let distance: f64 = (0..route.len())
+            .map(|k| matrix[route[k] as usize][route[(k + 1) % route.len()] as usize])
+            .sum();
+
+        incumbent.offer(&Solution { route, distance });
+        return;
+    }
+
+    let (i, j, _) = match node.select_edge() {
+        Some(edge) => edge,
+        None => return,
+    };
+
+    let mut included = node.clone();
+    included.include(i, j);
+
+    let mut excluded = node;
+    excluded.exclude(i, j);
+
+    if depth < PARALLEL_DEPTH {
+        rayon::join(|| reduced_matrix_recurse(included, matrix, incumbent, stats, depth + 1),
+            || reduced_matrix_recurse(excluded, matrix, incumbent, stats, depth + 1));
+    } else {
+        reduced_matrix_recurse(included, matrix, incumbent, stats, depth + 1);
+        reduced_matrix_recurse(excluded, matrix, incumbent, stats, depth + 1);
+    }
+}
+
+/* Little's branch and bound on an arbitrary cost matrix. matrix[i][j] is the cost of travelling
+*  from i to j, so asymmetric instances work the same as symmetric ones. Returns the best
+*  solution, which is `best_solution` itself if nothing beats `bssf`, along with the bound of
+*  the fully reduced root matrix. */
+pub fn get_reduced_matrix_bnb_from_matrix(matrix: &[Vec<f64>], bssf: f64, best_solution: &Solution, stats: &SearchStats) -> (Solution, f64) {
+    if matrix.len() < 3 {
+        return (best_solution.clone(), bssf);
+    }
+
+    let incumbent = Incumbent::new(Solution { route: best_solution.route.clone(), distance: bssf });
+
+    let root = ReducedNode::new(matrix);
+    let root_bound = root.bound;
+    reduced_matrix_recurse(root, matrix, &incumbent, stats, 0);
+
+    let solution = incumbent.into_solution();
+    if solution.distance >= bssf {
+        return (best_solution.clone(), root_bound);
+    }
+    (solution, root_bound)
+}
+
+/* Little's reduced cost matrix branch and bound on the distance map. */
+pub fn get_reduced_matrix_bnb(map: &DistanceMap, bssf: f64, best_solution: &Solution, stats: &SearchStats) -> (Solution, f64) {
+    let (mut solution, root_bound) = get_reduced_matrix_bnb_from_matrix(&map.get_matrix(), bssf, best_solution, stats);
+    solution.distance = crate::get_solution_length(map, &solution.route).0;
+    (solution, root_bound)
+}
@@ -0,0 +1,79 @@
+use rayon::prelude::*;
+
+use crate::distance_map::DistanceMap;
+use crate::greedy_edge::get_path_route;
+use crate::point::Points;
+use crate::priority_queue_structs::DisjointSet;
+use crate::solution::Solution;
+use crate::get_solution_length;
+
+/* The point closest to the centroid. Savings tours are best when the depot sits in the middle
+*  of the instance, since every saving is measured against trips out to it and back. */
+pub fn get_central_node(points: &Points) -> u32 {
+    let count = points.points.len() as f64;
+    let (sum_x, sum_y) = points.points.par_iter()
+        .map(|point| (point.x, point.y))
+        .reduce(|| (0.0, 0.0), |a, b| (a.0 + b.0, a.1 + b.1));
+    let (centre_x, centre_y) = (sum_x / count, sum_y / count);
+
+    points.points.par_iter()
+        .enumerate()
+        .map(|(node, point)| (node as u32, (point.x - centre_x).powi(2) + (point.y - centre_y).powi(2)))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .map_or(0, |(node, _)| node)
+}
+
+/* Clarke and Wright's savings construction. Every other node starts on its own trip out of the
+*  depot and back. Joining the trips that end at i and j saves d(depot, i) + d(depot, j) - d(i, j),
+*  so trips are joined by their ends, largest saving first, until one trip is left. */
+pub fn get_savings(map: &DistanceMap, depot: u32) -> Solution {
+    let count = map.point_count();
+
+    if count < 4 {
+        let route: Vec<u32> = (0..count as u32).collect();
+        let distance = get_solution_length(map, &route).0;
+        return Solution { route, distance };
+    }
+
+    let mut savings: Vec<(f64, u32, u32)> = (0..count as u32).into_par_iter()
+        .filter(|&i| i != depot)
+        .flat_map_iter(|i| (i + 1..count as u32).filter(move |&j| j != depot).map(move |j| {
+            let saving = map.get_distance_from_points(&depot, &i) + map.get_distance_from_points(&depot, &j) - map.get_distance_from_points(&i, &j);
+            (saving, i, j)
+        }))
+        .collect();
+    savings.par_sort_unstable_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+    // The trips' links between customers. A node with two links is inside a trip and can't be joined.
+    let mut neighbours: Vec<Vec<u32>> = vec![Vec::with_capacity(2); count];
+    let mut disjoint_set = DisjointSet::new(count);
+    let mut joined = 0;
+
+    for (_, i, j) in savings {
+        if neighbours[i as usize].len() == 2 || neighbours[j as usize].len() == 2 {
+            continue;
+        }
+
+        if disjoint_set.find(i) == disjoint_set.find(j) {
+            continue;
+        }
+
+        disjoint_set.union(i, j);
+        neighbours[i as usize].push(j);
+        neighbours[j as usize].push(i);
+        joined += 1;
+
+        if joined == count - 2 {
+            break;
+        }
+    }
+
+    // The last trip leaves the depot for one end and comes back from the other.
+    let start = (0..count as u32).find(|&node| node != depot && neighbours[node as usize].len() == 1).unwrap();
+    let mut route: Vec<u32> = vec![depot];
+    route.extend(get_path_route(&neighbours, start, count - 1));
+
+    let distance = get_solution_length(map, &route).0;
+
+    Solution { route, distance }
+}
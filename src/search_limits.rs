@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering as AtomicOrdering};
+use std::time::{Duration, Instant};
+
+/* Budget for an exact search. Either limit may be left unset. */
+#[derive(Clone, Copy, Debug)]
+pub struct SearchLimits {
+    pub deadline: Option<Instant>,
+    pub max_nodes: Option<u64>,
+}
+
+impl SearchLimits {
+    pub fn new(time: Option<Duration>, max_nodes: Option<u64>) -> SearchLimits {
+        SearchLimits { deadline: time.map(|time| Instant::now() + time), max_nodes }
+    }
+}
+
+/* Counters shared by every thread of an exact search. Once a limit is hit the search is marked
+*  as stopped, and every thread should unwind as soon as it notices. */
+pub struct SearchStats {
+    limits: SearchLimits,
+    nodes: AtomicU64,
+    pruned: AtomicU64,
+    stopped: AtomicBool,
+}
+
+impl SearchStats {
+    pub fn new(limits: SearchLimits) -> SearchStats {
+        SearchStats::with_counts(limits, 0, 0)
+    }
+
+    /* Stats of a resumed search, which keeps counting from where it left off. */
+    pub fn with_counts(limits: SearchLimits, nodes: u64, pruned: u64) -> SearchStats {
+        SearchStats { limits, nodes: AtomicU64::new(nodes), pruned: AtomicU64::new(pruned), stopped: AtomicBool::new(false) }
+    }
+
+    /* Count a visited node. Returns false if the search has run out of budget. */
+    pub fn visit(&self) -> bool {
+        self.visit_many(1)
+    }
+
+    pub fn visit_many(&self, count: u64) -> bool {
+        if self.stopped.load(AtomicOrdering::Relaxed) {
+            return false;
+        }
+
+        let nodes = self.nodes.fetch_add(count, AtomicOrdering::Relaxed) + count;
+
+        let out_of_nodes = self.limits.max_nodes.is_some_and(|max_nodes| nodes > max_nodes);
+        let out_of_time = self.limits.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+        if out_of_nodes || out_of_time {
+            self.stopped.store(true, AtomicOrdering::Relaxed);
+            return false;
+        }
+
+        true
+    }
+
+    pub fn prune(&self) {
+        self.pruned.fetch_add(1, AtomicOrdering::Relaxed);
+    }
+
+    /* Mark the search as incomplete without a limit being hit, e.g. after a numerical failure. */
+    pub fn stop(&self) {
+        self.stopped.store(true, AtomicOrdering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(AtomicOrdering::Relaxed)
+    }
+
+    pub fn get_nodes(&self) -> u64 {
+        self.nodes.load(AtomicOrdering::Relaxed)
+    }
+
+    pub fn get_pruned(&self) -> u64 {
+        self.pruned.load(AtomicOrdering::Relaxed)
+    }
+}
@@ -1,6 +1,11 @@
-use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::Mutex;
 
-#[derive(Clone, Debug, Serialize)]
+use serde::{Deserialize, Serialize};
+
+use crate::open_path::PathEnds;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Solution {
     pub route: Vec<u32>,
     pub distance: f64,
@@ -14,4 +19,64 @@ impl Solution {
     pub fn new() -> Solution {
         Solution {route: Vec::new(), distance: 0.0 }
     }
-}
\ No newline at end of file
+}
+
+/* Shared incumbent. The distance is mirrored into an atomic so workers can read the bound and
+*  check whether they improved on it without taking the lock, which is only held to swap in a
+*  better route. */
+pub struct Incumbent {
+    distance: AtomicU64,
+    solution: Mutex<Solution>,
+}
+
+impl Incumbent {
+    pub fn new(solution: Solution) -> Incumbent {
+        Incumbent { distance: AtomicU64::new(solution.distance.to_bits()), solution: Mutex::new(solution) }
+    }
+
+    pub fn get_distance(&self) -> f64 {
+        f64::from_bits(self.distance.load(AtomicOrdering::Relaxed))
+    }
+
+    pub fn offer(&self, solution: &Solution) {
+        if solution.distance >= self.get_distance() {
+            return;
+        }
+
+        let mut solution_guard = self.solution.lock().unwrap();
+        if solution.distance < solution_guard.distance {
+            *solution_guard = solution.clone();
+            self.distance.store(solution.distance.to_bits(), AtomicOrdering::Relaxed);
+        }
+    }
+
+    pub fn get_solution(&self) -> Solution {
+        self.solution.lock().unwrap().clone()
+    }
+
+    pub fn into_solution(self) -> Solution {
+        self.solution.into_inner().unwrap()
+    }
+}
+
+/* What an exact search managed to prove about its solution. */
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchReport {
+    pub lower_bound: f64,
+    pub gap: f64,
+    pub nodes_explored: u64,
+    pub nodes_pruned: u64,
+    pub proven_optimal: bool,
+}
+
+/* Solution together with its search report, written out as one flat JSON object. Open paths
+*  also record their fixed ends. */
+#[derive(Serialize)]
+pub struct ReportedSolution<'a> {
+    #[serde(flatten)]
+    pub solution: &'a Solution,
+    #[serde(flatten)]
+    pub report: Option<&'a SearchReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_path: Option<&'a PathEnds>,
+}
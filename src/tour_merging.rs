@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+
+use crate::distance_map::DistanceMap;
+use crate::search_limits::SearchStats;
+use crate::solution::Solution;
+use crate::get_solution_length;
+
+/* Margin a bound has to stay under the best tour by before its subtree is searched. */
+const BOUND_EPSILON: f64 = 1e-7;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum EdgeState {
+    Free,
+    Included,
+    Excluded,
+}
+
+/* Every edge used by at least one of the tours. */
+struct UnionGraph {
+    edges: Vec<(u32, u32)>,
+    costs: Vec<f64>,
+    frequencies: Vec<usize>,
+    /* Edges touching each node, cheapest first. */
+    incident: Vec<Vec<usize>>,
+}
+
+impl UnionGraph {
+    fn new(map: &DistanceMap, tours: &[Solution]) -> UnionGraph {
+        let count = map.point_count();
+        let mut frequency: HashMap<(u32, u32), usize> = HashMap::new();
+
+        for tour in tours {
+            for i in 0..tour.route.len() {
+                let (a, b) = (tour.route[i], tour.route[(i + 1) % tour.route.len()]);
+                *frequency.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+
+        let mut edges: Vec<((u32, u32), usize)> = frequency.into_iter().collect();
+        edges.sort_unstable();
+
+        let costs: Vec<f64> = edges.iter().map(|((a, b), _)| map.get_distance_from_points(a, b)).collect();
+        let mut incident: Vec<Vec<usize>> = vec![Vec::new(); count];
+        for (id, ((a, b), _)) in edges.iter().enumerate() {
+            incident[*a as usize].push(id);
+            incident[*b as usize].push(id);
+        }
+        for list in incident.iter_mut() {
+            list.sort_by(|&x, &y| costs[x].partial_cmp(&costs[y]).unwrap());
+        }
+
+        UnionGraph {
+            edges: edges.iter().map(|(edge, _)| *edge).collect(),
+            costs,
+            frequencies: edges.iter().map(|(_, frequency)| *frequency).collect(),
+            incident,
+        }
+    }
+}
+
+/* A node of the search. Included edges always form paths, and `other_end` links the two ends
+*  of every path, so an edge that would close a cycle too early is caught in O(1). */
+#[derive(Clone)]
+struct MergeState {
+    states: Vec<EdgeState>,
+    degree: Vec<u8>,
+    free: Vec<u8>,
+    other_end: Vec<u32>,
+    included: usize,
+    cost: f64,
+}
+
+impl MergeState {
+    fn new(graph: &UnionGraph) -> MergeState {
+        let count = graph.incident.len();
+
+        MergeState {
+            states: vec![EdgeState::Free; graph.edges.len()],
+            degree: vec![0; count],
+            free: graph.incident.iter().map(|list| list.len() as u8).collect(),
+            other_end: (0..count as u32).collect(),
+            included: 0,
+            cost: 0.0,
+        }
+    }
+
+    /* Returns false if the edge can't be part of a tour alongside the edges included so far. */
+    fn include(&mut self, graph: &UnionGraph, edge: usize, touched: &mut Vec<u32>) -> bool {
+        let (a, b) = graph.edges[edge];
+        let count = self.degree.len();
+
+        if self.degree[a as usize] == 2 || self.degree[b as usize] == 2 {
+            return false;
+        }
+        if self.other_end[a as usize] == b && self.included + 1 < count {
+            return false;
+        }
+
+        self.states[edge] = EdgeState::Included;
+        self.included += 1;
+        self.cost += graph.costs[edge];
+        for node in [a, b] {
+            self.degree[node as usize] += 1;
+            self.free[node as usize] -= 1;
+            touched.push(node);
+        }
+
+        let (end1, end2) = (self.other_end[a as usize], self.other_end[b as usize]);
+        self.other_end[end1 as usize] = end2;
+        self.other_end[end2 as usize] = end1;
+        touched.push(end1);
+        touched.push(end2);
+
+        true
+    }
+
+    /* Returns false if an end of the edge is left without enough edges for a tour. */
+    fn exclude(&mut self, graph: &UnionGraph, edge: usize, touched: &mut Vec<u32>) -> bool {
+        let (a, b) = graph.edges[edge];
+
+        self.states[edge] = EdgeState::Excluded;
+        for node in [a, b] {
+            self.free[node as usize] -= 1;
+            touched.push(node);
+        }
+
+        self.degree[a as usize] + self.free[a as usize] >= 2 && self.degree[b as usize] + self.free[b as usize] >= 2
+    }
+
+    /* Follow the consequences of the touched nodes' changes until nothing more is forced. A node
+    *  with two edges loses the rest, a node with exactly two options takes both, and an edge that
+    *  joins the two ends of one path is dropped unless it completes the tour. */
+    fn propagate(&mut self, graph: &UnionGraph, touched_input: Vec<u32>) -> bool {
+        let count = self.degree.len();
+        let mut touched = touched_input;
+
+        while let Some(node) = touched.pop() {
+            for &edge in graph.incident[node as usize].iter() {
+                if self.states[edge] != EdgeState::Free {
+                    continue;
+                }
+
+                let (a, b) = graph.edges[edge];
+                let other = if a == node { b } else { a };
+                let closes_early = self.other_end[node as usize] == other && self.included + 1 < count;
+
+                let feasible = if self.degree[node as usize] == 2 || closes_early {
+                    self.exclude(graph, edge, &mut touched)
+                } else if self.degree[node as usize] + self.free[node as usize] == 2 {
+                    self.include(graph, edge, &mut touched)
+                } else {
+                    true
+                };
+
+                if !feasible {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /* Every node needs two edges, so half the sum over nodes of their two cheapest possible edges
+    *  bounds any tour that completes this state. */
+    fn get_bound(&self, graph: &UnionGraph) -> f64 {
+        let mut total = 0.0;
+
+        for (node, list) in graph.incident.iter().enumerate() {
+            let mut needed = 2 - self.degree[node] as usize;
+
+            for &edge in list.iter() {
+                match self.states[edge] {
+                    EdgeState::Included => total += graph.costs[edge],
+                    EdgeState::Free if needed > 0 => {
+                        total += graph.costs[edge];
+                        needed -= 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        total / 2.0
+    }
+
+    /* The free edge to decide next. Taken at the node with the fewest options left, and the edge
+    *  most of the tours agree on is tried first. */
+    fn get_branch_edge(&self, graph: &UnionGraph) -> Option<usize> {
+        let node = (0..self.degree.len())
+            .filter(|&node| self.degree[node] < 2 && self.free[node] > 0)
+            .min_by_key(|&node| self.free[node])?;
+
+        graph.incident[node].iter()
+            .copied()
+            .filter(|&edge| self.states[edge] == EdgeState::Free)
+            .max_by(|&x, &y| graph.frequencies[x].cmp(&graph.frequencies[y]).then(graph.costs[y].partial_cmp(&graph.costs[x]).unwrap()))
+    }
+
+    fn get_route(&self, graph: &UnionGraph) -> Vec<u32> {
+        let count = self.degree.len();
+        let mut neighbours: Vec<Vec<u32>> = vec![Vec::with_capacity(2); count];
+        for (edge, state) in self.states.iter().enumerate() {
+            if *state == EdgeState::Included {
+                let (a, b) = graph.edges[edge];
+                neighbours[a as usize].push(b);
+                neighbours[b as usize].push(a);
+            }
+        }
+
+        let mut route: Vec<u32> = vec![0];
+        let mut previous = 0;
+        while route.len() < count {
+            let current = *route.last().unwrap();
+            let next = if neighbours[current as usize][0] != previous || route.len() == 1 {
+                neighbours[current as usize][0]
+            } else {
+                neighbours[current as usize][1]
+            };
+            previous = current;
+            route.push(next);
+        }
+
+        route
+    }
+}
+
+fn search(graph: &UnionGraph, state: MergeState, best: &mut Option<MergeState>, best_cost: &mut f64, stats: &SearchStats) {
+    if !stats.visit() {
+        return;
+    }
+
+    if state.get_bound(graph) >= *best_cost - BOUND_EPSILON {
+        stats.prune();
+        return;
+    }
+
+    if state.included == state.degree.len() {
+        *best_cost = state.cost;
+        *best = Some(state);
+        return;
+    }
+
+    let Some(edge) = state.get_branch_edge(graph) else {
+        return;
+    };
+
+    for include in [true, false] {
+        let mut child = state.clone();
+        let mut touched: Vec<u32> = Vec::new();
+
+        let feasible = if include {
+            child.include(graph, edge, &mut touched)
+        } else {
+            child.exclude(graph, edge, &mut touched)
+        };
+
+        if feasible && child.propagate(graph, touched) {
+            search(graph, child, best, best_cost, stats);
+        }
+    }
+}
+
+/* Print how many of the tours share each edge of their union. */
+fn print_edge_frequencies(graph: &UnionGraph, tour_count: usize) {
+    let mut histogram: Vec<usize> = vec![0; tour_count + 1];
+    for &frequency in graph.frequencies.iter() {
+        histogram[frequency] += 1;
+    }
+
+    println!("The {} tours use {} distinct edges.", tour_count, graph.edges.len());
+    for (frequency, edges) in histogram.iter().enumerate().skip(1).rev() {
+        println!("Edges in {} of the tours: {}", frequency, edges);
+    }
+}
+
+/* Tour merging. Finds the shortest tour that only uses edges of the given tours, by branch and
+*  bound over their union. The union of a few good tours is sparse and mostly agrees with
+*  itself, so nodes with only two edges settle most of the tour before any branching. Returns
+*  the merged tour, or the best of the given tours if nothing shorter is found, and whether the
+*  search finished within the limits, which makes the tour optimal over the union. */
+pub fn get_merged_tour(map: &DistanceMap, tours: &[Solution], stats: &SearchStats) -> (Solution, bool) {
+    let best_tour = tours.iter().min_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap()).unwrap().clone();
+
+    if map.point_count() < 4 || tours.len() < 2 {
+        return (best_tour, true);
+    }
+
+    let graph = UnionGraph::new(map, tours);
+    print_edge_frequencies(&graph, tours.len());
+
+    let mut best: Option<MergeState> = None;
+    let mut best_cost = best_tour.distance;
+
+    let mut root = MergeState::new(&graph);
+    let touched: Vec<u32> = (0..map.point_count() as u32).collect();
+    if root.propagate(&graph, touched) {
+        search(&graph, root, &mut best, &mut best_cost, stats);
+    }
+
+    let proven = !stats.is_stopped();
+
+    match best {
+        Some(state) => {
+            let route = state.get_route(&graph);
+            let distance = get_solution_length(map, &route).0;
+            (Solution { route, distance }, proven)
+        }
+        None => (best_tour, proven),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_limits::SearchLimits;
+    use crate::tests::get_random_points;
+
+    #[test]
+    fn merging_never_lengthens_the_best_tour() {
+        for seed in 0..4 {
+            let map = DistanceMap::new(&get_random_points(60, seed));
+
+            let tours: Vec<Solution> = (0..6).map(|_| crate::get_two_opt(&map, crate::get_random_solution(&map))).collect();
+            let shortest = tours.iter().map(|tour| tour.distance).fold(f64::INFINITY, f64::min);
+
+            let stats = SearchStats::new(SearchLimits::new(None, None));
+            let (merged, proven) = get_merged_tour(&map, &tours, &stats);
+
+            let mut route = merged.route.clone();
+            route.sort_unstable();
+            assert_eq!(route, (0..60).collect::<Vec<u32>>());
+            assert!((get_solution_length(&map, &merged.route).0 - merged.distance).abs() < 1e-6);
+            assert!(merged.distance <= shortest + 1e-6);
+            assert!(proven);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use rayon::prelude::*;
+
+use crate::distance_map::DistanceMap;
+use crate::held_karp::get_held_karp_path;
+use crate::search_limits::{SearchLimits, SearchStats};
+use crate::solution::Solution;
+use crate::get_solution_length;
+
+/* Largest window solved exactly. Held-Karp over a window of k cities takes O(2^(k-2) k^2) time. */
+const MAX_WINDOW: usize = 16;
+
+/* Passes over the tour at most. */
+const MAX_PASSES: usize = 20;
+
+/* Margin a reordered window has to win by, so rounding error never swaps equal paths. */
+const IMPROVEMENT_EPSILON: f64 = 1e-9;
+
+/* Reorder the window's interior cities optimally, keeping its first and last city in place.
+*  Returns how much shorter the window's path became. */
+fn optimize_window(map: &DistanceMap, window: &mut [u32]) -> f64 {
+    let count = window.len();
+
+    if count < 4 {
+        return 0.0;
+    }
+
+    let matrix: Vec<Vec<f64>> = window.iter()
+        .map(|a| window.iter().map(|b| map.get_distance_from_points(a, b)).collect())
+        .collect();
+
+    let current: f64 = (0..count - 1).map(|i| matrix[i][i + 1]).sum();
+    let stats = SearchStats::new(SearchLimits::new(None, None));
+    let path = get_held_karp_path(&matrix, &stats).unwrap();
+    let best: f64 = path.windows(2).map(|pair| matrix[pair[0]][pair[1]]).sum();
+
+    if best >= current - IMPROVEMENT_EPSILON {
+        return 0.0;
+    }
+
+    let original = window.to_vec();
+    for (city, local) in window.iter_mut().zip(path) {
+        *city = original[local];
+    }
+
+    current - best
+}
+
+/* Slide windows of `window_size` consecutive cities along the tour and solve each exactly with
+*  its ends fixed. Windows of one pass don't overlap, so they are solved in parallel, and every
+*  pass shifts them by half a window so the seams between them get optimized too. Stops once
+*  two passes in a row find nothing, or after MAX_PASSES. */
+pub fn get_windowed_exact(map: &DistanceMap, solution: Solution, window_size: usize) -> Solution {
+    let count = map.point_count();
+    let window_size = window_size.clamp(4, MAX_WINDOW).min(count);
+    let mut route = solution.route;
+
+    if count < 5 {
+        let distance = get_solution_length(map, &route).0;
+        return Solution { route, distance };
+    }
+
+    let mut idle_passes = 0;
+    for _ in 0..MAX_PASSES {
+        let improvement: f64 = route.par_chunks_mut(window_size)
+            .map(|window| optimize_window(map, window))
+            .sum();
+
+        idle_passes = if improvement > 0.0 { 0 } else { idle_passes + 1 };
+        if idle_passes == 2 {
+            break;
+        }
+
+        route.rotate_left(window_size / 2);
+    }
+
+    let distance = get_solution_length(map, &route).0;
+
+    Solution { route, distance }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::get_random_points;
+
+    #[test]
+    fn windows_never_lengthen_the_tour() {
+        for seed in 0..4 {
+            let map = DistanceMap::new(&get_random_points(120, seed));
+
+            for solution in [crate::get_random_solution(&map), crate::get_two_opt(&map, crate::get_random_solution(&map))] {
+                for window_size in [4, 9, 12] {
+                    let windowed = get_windowed_exact(&map, solution.clone(), window_size);
+
+                    let mut route = windowed.route.clone();
+                    route.sort_unstable();
+                    assert_eq!(route, (0..120).collect::<Vec<u32>>());
+                    assert!((get_solution_length(&map, &windowed.route).0 - windowed.distance).abs() < 1e-6);
+                    assert!(windowed.distance <= solution.distance + 1e-6);
+                }
+            }
+        }
+    }
+}